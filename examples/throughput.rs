@@ -0,0 +1,57 @@
+//! Measures the block encryption and decryption speed of each AES backend.
+//!
+//! Run with `cargo run --release --example throughput`.
+
+use std::time::Instant;
+
+use cryptopals_rs::aes::{Aes128ECB, Backend};
+
+const LEN: usize = 16 * 1024 * 1024;
+
+fn main() {
+    let key = b"YELLOW SUBMARINE";
+    let mut buf = vec![0u8; LEN];
+
+    for backend in [
+        Backend::Reference,
+        Backend::TTable,
+        Backend::Bitsliced,
+        Backend::AesNi,
+    ] {
+        if !backend.is_available() {
+            continue;
+        }
+
+        let cipher = Aes128ECB::new(key).with_backend(backend);
+
+        report(
+            &format!("{backend:?}"),
+            &mut buf,
+            |buf| cipher.encrypt_in_place(buf).unwrap(),
+            |buf| cipher.decrypt_in_place(buf).unwrap(),
+        );
+        report(
+            &format!("{backend:?}, threaded"),
+            &mut buf,
+            |buf| cipher.encrypt_in_place_threaded(buf).unwrap(),
+            |buf| cipher.decrypt_in_place_threaded(buf).unwrap(),
+        );
+    }
+}
+
+fn report(name: &str, buf: &mut [u8], encrypt: impl Fn(&mut [u8]), decrypt: impl Fn(&mut [u8])) {
+    let start = Instant::now();
+    encrypt(buf);
+    let encrypt = start.elapsed();
+
+    let start = Instant::now();
+    decrypt(buf);
+    let decrypt = start.elapsed();
+
+    let mb = LEN as f64 / (1024.0 * 1024.0);
+    println!(
+        "{name}: encrypt {:.1} MB/s, decrypt {:.1} MB/s",
+        mb / encrypt.as_secs_f64(),
+        mb / decrypt.as_secs_f64(),
+    );
+}
@@ -0,0 +1,63 @@
+//! Times block encryptions grouped by the first plaintext byte, for each AES
+//! backend.
+//!
+//! Table based backends touch different cache lines depending on
+//! `plaintext[0] ^ key[0]`, which can show up as a spread between the fastest
+//! and the slowest group. The backend with the bitsliced S-box should show
+//! only noise.
+//!
+//! Run with `cargo run --release --example timing`.
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use cryptopals_rs::aes::{AesCore, Backend, Key128};
+use cryptopals_rs::modes::BlockCipher;
+
+const SAMPLES: usize = 4096;
+
+fn main() {
+    let key = b"YELLOW SUBMARINE";
+
+    for backend in [
+        Backend::Reference,
+        Backend::TTable,
+        Backend::Bitsliced,
+        Backend::AesNi,
+    ] {
+        if !backend.is_available() {
+            continue;
+        }
+
+        let cipher = AesCore::<Key128>::new(key).with_backend(backend);
+        let mut samples: Vec<Vec<u64>> = (0..256).map(|_| Vec::with_capacity(SAMPLES)).collect();
+
+        // Interleave the byte values so that frequency scaling and other
+        // background noise hits all groups alike.
+        for sample in 0..SAMPLES {
+            for (value, times) in samples.iter_mut().enumerate() {
+                let mut block = [0u8; 16];
+                block[0] = value as u8;
+                block[1..9].copy_from_slice(&(sample as u64).to_le_bytes());
+
+                let start = Instant::now();
+                cipher.encrypt_block(black_box(&mut block));
+                times.push(start.elapsed().as_nanos() as u64);
+            }
+        }
+
+        // Medians, as single measurements are often blown up by interrupts.
+        let medians: Vec<u64> = samples
+            .iter_mut()
+            .map(|times| {
+                times.sort_unstable();
+                times[SAMPLES / 2]
+            })
+            .collect();
+        let min = *medians.iter().min().unwrap();
+        let max = *medians.iter().max().unwrap();
+        let slowest = medians.iter().position(|&m| m == max).unwrap();
+
+        println!("{backend:?}: median {min}..{max} ns, slowest first byte {slowest:#04x}");
+    }
+}
@@ -1,3 +1,21 @@
+#[cfg(target_arch = "x86_64")]
+mod aesni;
+mod bitsliced;
+mod keywrap;
+mod padding;
+pub mod square;
+mod ttable;
+
+use crate::mac::{CbcMac, Cmac};
+use crate::modes::{BlockCipher, Cipher};
+
+pub use crate::modes::{
+    AuthenticationError, CiphertextStealing, CounterLayout, GHash, Gf128, Siv, Xts, CBC, CBCCS,
+    CCM, CFB, CFB8, CTR, ECB, GCM, OFB, PCBC,
+};
+pub use keywrap::{KeyWrapError, KW, KWP};
+pub use padding::{AnsiX923, Iso10126, Iso7816, Padding, Pkcs7, ZeroPadding};
+
 pub fn pad(block: &[u8], required_block_size: usize) -> Vec<u8> {
     let mut res = block.to_vec();
     pad_in_place(&mut res, required_block_size);
@@ -6,52 +24,356 @@ pub fn pad(block: &[u8], required_block_size: usize) -> Vec<u8> {
 }
 
 pub fn pad_in_place(block: &mut Vec<u8>, required_block_size: usize) {
-    let len = block.len();
-    let padding = required_block_size - (len % required_block_size);
-    let new_len = if padding == 0 {
-        len + required_block_size
-    } else {
-        len + padding
-    };
+    Pkcs7::pad(block, required_block_size)
+}
+
+/// Returns a copy of `block` with the PKCS#7 padding removed.
+pub fn unpad(block: &[u8], block_size: usize) -> Result<Vec<u8>, PaddingError> {
+    let len = validate_padding(block, block_size)?;
 
-    block.resize(new_len, padding as u8);
+    Ok(block[..len].to_vec())
 }
 
-pub fn unpad(_block: &[u8]) -> Vec<u8> {
-    unimplemented!()
+/// Strips the PKCS#7 padding from `block`.
+///
+/// The buffer is left untouched if the padding is invalid.
+pub fn unpad_in_place(block: &mut Vec<u8>, block_size: usize) -> Result<(), PaddingError> {
+    let len = validate_padding(block, block_size)?;
+    block.truncate(len);
+
+    Ok(())
+}
+
+/// Checks that `block` ends with valid PKCS#7 padding and returns the length
+/// of the data without the padding.
+///
+/// This does not modify the input, which makes it usable as the check behind
+/// a padding oracle.
+pub fn validate_padding(block: &[u8], block_size: usize) -> Result<usize, PaddingError> {
+    Pkcs7::unpadded_len(block, block_size)
 }
 
-pub fn unpad_in_place(_block: &mut [u8]) {
-    unimplemented!()
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum PaddingError {
+    #[error("Cannot unpad an empty buffer")]
+    Empty,
+    #[error("Invalid pad byte {0:#x}")]
+    InvalidPadByte(u8),
+    #[error("Pad bytes are not all equal")]
+    InconsistentPadding,
+    #[error("Padded buffer is not a multiple of the block size")]
+    NotBlockAligned,
 }
 
+pub type Aes128<MODE> = Aes<Key128, MODE>;
+pub type Aes192<MODE> = Aes<Key192, MODE>;
+pub type Aes256<MODE> = Aes<Key256, MODE>;
+
 pub type Aes128ECB = Aes128<ECB>;
 pub type Aes128CBC = Aes128<CBC>;
+pub type Aes192ECB = Aes192<ECB>;
+pub type Aes192CBC = Aes192<CBC>;
+pub type Aes256ECB = Aes256<ECB>;
+pub type Aes256CBC = Aes256<CBC>;
+pub type Aes128CTR = Aes128<CTR>;
+pub type Aes192CTR = Aes192<CTR>;
+pub type Aes256CTR = Aes256<CTR>;
+pub type Aes128CBCCS = Aes128<CBCCS>;
+pub type Aes128CFB = Aes128<CFB>;
+pub type Aes128CFB8 = Aes128<CFB8>;
+pub type Aes128OFB = Aes128<OFB>;
+pub type Aes128PCBC = Aes128<PCBC>;
+pub type Aes128GCM = Aes128<GCM>;
+pub type Aes192GCM = Aes192<GCM>;
+pub type Aes256GCM = Aes256<GCM>;
+pub type Aes128CCM = Aes128<CCM>;
+pub type Aes192CCM = Aes192<CCM>;
+pub type Aes256CCM = Aes256<CCM>;
+pub type Aes128KW = Aes128<KW>;
+pub type Aes192KW = Aes192<KW>;
+pub type Aes256KW = Aes256<KW>;
+pub type Aes128KWP = Aes128<KWP>;
+pub type Aes192KWP = Aes192<KWP>;
+pub type Aes256KWP = Aes256<KWP>;
+pub type Aes128CbcMac = CbcMac<AesCore<Key128>>;
+pub type Aes128Cmac = Cmac<AesCore<Key128>>;
+pub type Aes128Siv = Siv<AesCore<Key128>>;
+pub type Aes256Siv = Siv<AesCore<Key256>>;
+pub type Aes128Xts = Xts<AesCore<Key128>>;
+pub type Aes256Xts = Xts<AesCore<Key256>>;
+
+/// The key length of an AES variant, in 32-bit words, and the number of rounds
+/// it uses.
+pub trait KeySize {
+    const NK: usize;
+    const NR: usize;
+}
+
+pub struct Key128;
+pub struct Key192;
+pub struct Key256;
+
+impl KeySize for Key128 {
+    const NK: usize = 4;
+    const NR: usize = 10;
+}
+
+impl KeySize for Key192 {
+    const NK: usize = 6;
+    const NR: usize = 12;
+}
+
+impl KeySize for Key256 {
+    const NK: usize = 8;
+    const NR: usize = 14;
+}
 
-pub struct Aes128<MODE> {
-    round_keys: [u32; NB * (NR + 1)],
-    _marker: std::marker::PhantomData<MODE>,
+/// AES with the key size `K`, used in the mode of operation `MODE`.
+pub type Aes<K, MODE> = Cipher<AesCore<K>, MODE>;
+
+impl<K: KeySize, MODE> Aes<K, MODE> {
+    /// # Panics
+    /// The function panics if the key has the wrong length for `K`.
+    pub fn new(key: &[u8]) -> Self {
+        Self::from_cipher(AesCore::new(key))
+    }
+
+    pub fn try_new(key: &[u8]) -> Result<Self, crate::Error> {
+        Ok(Self::from_cipher(AesCore::try_new(key)?))
+    }
+
+    /// Expands `key` with `backend` as well, see
+    /// [`AesCore::try_new_with_backend`].
+    pub fn try_new_with_backend(key: &[u8], backend: Backend) -> Result<Self, crate::Error> {
+        Ok(Self::from_cipher(AesCore::try_new_with_backend(
+            key, backend,
+        )?))
+    }
+
+    /// Switches the implementation of the block function.
+    pub fn with_backend(self, backend: Backend) -> Self {
+        Self::from_cipher(self.into_cipher().with_backend(backend))
+    }
+
+    /// Reduces the number of rounds, see [`AesCore::with_rounds`].
+    pub fn with_rounds(self, rounds: usize) -> Self {
+        Self::from_cipher(self.into_cipher().with_rounds(rounds))
+    }
+}
+
+impl<K: KeySize> Siv<AesCore<K>> {
+    /// `key` is the CMAC key followed by the CTR key, so [`Aes128Siv`] takes
+    /// 32 bytes, like `AEAD_AES_SIV_CMAC_256` in RFC 5297.
+    ///
+    /// # Panics
+    /// The function panics if `key` has the wrong length.
+    pub fn new(key: &[u8]) -> Self {
+        Self::try_new(key).expect("invalid key length")
+    }
+
+    pub fn try_new(key: &[u8]) -> Result<Self, crate::Error> {
+        let (mac, ctr) = split_double_key(key)?;
+        Ok(Self::from_ciphers(mac, ctr))
+    }
 }
 
-impl<MODE> Aes128<MODE> {
+impl<K: KeySize> Xts<AesCore<K>> {
+    /// `key` is the data key followed by the tweak key, as in IEEE 1619.
+    ///
+    /// # Panics
+    /// The function panics if `key` has the wrong length.
     pub fn new(key: &[u8]) -> Self {
-        let round_keys = create_round_keys(key);
-        Self {
+        Self::try_new(key).expect("invalid key length")
+    }
+
+    pub fn try_new(key: &[u8]) -> Result<Self, crate::Error> {
+        let (data, tweak) = split_double_key(key)?;
+        Ok(Self::from_ciphers(data, tweak))
+    }
+}
+
+/// Expands the two halves of a key twice as long as a `K` key, for the modes
+/// that take two independent keys.
+fn split_double_key<K: KeySize>(key: &[u8]) -> Result<(AesCore<K>, AesCore<K>), crate::Error> {
+    if key.len() != 8 * K::NK {
+        return Err(crate::Error::InvalidKeyLength {
+            len: key.len(),
+            expected: 8 * K::NK,
+        });
+    }
+
+    let (first, second) = key.split_at(4 * K::NK);
+    Ok((AesCore::try_new(first)?, AesCore::try_new(second)?))
+}
+
+/// The implementation used for the AES block function.
+///
+/// All backends compute the same permutation and can be switched freely, as
+/// long as the CPU supports them. The default is [`Backend::detect`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// A direct translation of the round functions in FIPS-197.
+    Reference,
+    /// Precomputed tables combining the round functions into 32-bit lookups.
+    TTable,
+    /// The byte-oriented reference rounds, with a bitsliced S-box that
+    /// evaluates `SubBytes` for one block as a boolean circuit instead of
+    /// looking up a table. Only the S-box is bitsliced, so this is slower
+    /// than [`Backend::Reference`], but no memory access depends on secret
+    /// data. The key expansion only avoids the table too when the cipher is
+    /// created with [`AesCore::try_new_with_backend`].
+    Bitsliced,
+    /// The AES-NI instructions of x86_64 processors.
+    AesNi,
+}
+
+impl Backend {
+    /// Picks AES-NI if the CPU has it, and the reference code otherwise.
+    pub fn detect() -> Self {
+        if Self::AesNi.is_available() {
+            Self::AesNi
+        } else {
+            Self::Reference
+        }
+    }
+
+    /// Whether the backend can run on this CPU.
+    pub fn is_available(self) -> bool {
+        match self {
+            Self::AesNi => {
+                #[cfg(target_arch = "x86_64")]
+                return aesni::is_available();
+                #[cfg(not(target_arch = "x86_64"))]
+                return false;
+            }
+            Self::Reference | Self::TTable | Self::Bitsliced => true,
+        }
+    }
+}
+
+impl Default for Backend {
+    fn default() -> Self {
+        Self::detect()
+    }
+}
+
+/// The AES block cipher on its own, without a mode of operation.
+pub struct AesCore<K> {
+    round_keys: Vec<u32>,
+    // Round keys for the equivalent inverse cipher, used by table based
+    // decryption.
+    inv_round_keys: Vec<u32>,
+    backend: Backend,
+    _marker: std::marker::PhantomData<K>,
+}
+
+impl<K: KeySize> AesCore<K> {
+    /// # Panics
+    /// The function panics if the key has the wrong length for `K`.
+    pub fn new(key: &[u8]) -> Self {
+        Self::try_new(key).expect("invalid key length")
+    }
+
+    pub fn try_new(key: &[u8]) -> Result<Self, crate::Error> {
+        Self::try_new_with_backend(key, Backend::default())
+    }
+
+    /// Uses `backend` for both the key expansion and the block function.
+    ///
+    /// Unlike [`AesCore::with_backend`], this keeps the key expansion of
+    /// [`Backend::Bitsliced`] free of table lookups.
+    ///
+    /// # Panics
+    /// The function panics if the CPU does not support `backend`.
+    pub fn try_new_with_backend(key: &[u8], backend: Backend) -> Result<Self, crate::Error> {
+        if key.len() != 4 * K::NK {
+            return Err(crate::Error::InvalidKeyLength {
+                len: key.len(),
+                expected: 4 * K::NK,
+            });
+        }
+
+        assert!(
+            backend.is_available(),
+            "{backend:?} is not supported by this CPU"
+        );
+        let round_keys = match backend {
+            Backend::AesNi => create_round_keys_aesni(key).expect("AES-NI is available"),
+            Backend::Bitsliced => expand_key(key, bitsliced::sub_word),
+            Backend::Reference | Backend::TTable => create_round_keys(key),
+        };
+        let inv_round_keys = ttable::decryption_round_keys(&round_keys);
+        Ok(Self {
             round_keys,
+            inv_round_keys,
+            backend,
             _marker: std::marker::PhantomData,
-        }
+        })
     }
 
-    fn decrypt_block(&self, input: &[u8], output: &mut [u8], state: &mut [u8; 16]) {
-        state.copy_from_slice(input);
+    /// Switches the implementation of the block function.
+    ///
+    /// The key schedule is kept as it is, so it was expanded by the default
+    /// backend. Use [`AesCore::try_new_with_backend`] to expand it without
+    /// table lookups for [`Backend::Bitsliced`].
+    ///
+    /// # Panics
+    /// The function panics if the CPU does not support `backend`.
+    pub fn with_backend(mut self, backend: Backend) -> Self {
+        assert!(
+            backend.is_available(),
+            "{backend:?} is not supported by this CPU"
+        );
+        self.backend = backend;
+        self
+    }
 
-        add_round_key(state, self.round_keys[NR * NB..].try_into().unwrap());
+    pub fn backend(&self) -> Backend {
+        self.backend
+    }
+
+    /// Cuts the cipher down to its first `rounds` rounds, for cryptanalysis.
+    ///
+    /// The last of the remaining rounds skips `MixColumns` like the last round
+    /// of the full cipher, and the round keys are the first `rounds + 1` of the
+    /// regular key schedule.
+    ///
+    /// # Panics
+    /// The function panics if `rounds` is 0 or more than the current number of
+    /// rounds.
+    pub fn with_rounds(mut self, rounds: usize) -> Self {
+        assert!(
+            (1..=self.rounds()).contains(&rounds),
+            "cannot reduce {} rounds to {rounds}",
+            self.rounds()
+        );
+
+        self.round_keys.truncate(NB * (rounds + 1));
+        self.inv_round_keys = ttable::decryption_round_keys(&self.round_keys);
+        self
+    }
+
+    /// The number of rounds, which is `K::NR` unless reduced with
+    /// [`AesCore::with_rounds`].
+    pub fn rounds(&self) -> usize {
+        self.round_keys.len() / NB - 1
+    }
+
+    fn decrypt_rounds(
+        &self,
+        state: &mut [u8; BLOCK_SIZE],
+        inv_sub_bytes: impl Fn(&mut [u8; BLOCK_SIZE]),
+    ) {
+        add_round_key(
+            state,
+            self.round_keys[self.rounds() * NB..].try_into().unwrap(),
+        );
 
         for round_key in self
             .round_keys
             .rchunks_exact(NB)
             .skip(1) // already added one rk
-            .take(9) // add rk at end
+            .take(self.rounds() - 1) // add rk at end
             .map(|rk| TryInto::<&[u32; NB]>::try_into(rk).unwrap())
         {
             inv_shift_rows(state);
@@ -63,20 +385,20 @@ impl<MODE> Aes128<MODE> {
         inv_shift_rows(state);
         inv_sub_bytes(state);
         add_round_key(state, self.round_keys[0..NB].try_into().unwrap());
-
-        output.copy_from_slice(state)
     }
 
-    fn encrypt_block(&self, input: &[u8], output: &mut [u8], state: &mut [u8; 16]) {
-        state.copy_from_slice(input);
-
+    fn encrypt_rounds(
+        &self,
+        state: &mut [u8; BLOCK_SIZE],
+        sub_bytes: impl Fn(&mut [u8; BLOCK_SIZE]),
+    ) {
         add_round_key(state, self.round_keys[0..NB].try_into().unwrap());
 
         for round_key in self
             .round_keys
             .chunks_exact(NB)
             .skip(1) // already added one rk
-            .take(9) // add rk at end
+            .take(self.rounds() - 1) // add rk at end
             .map(|rk| TryInto::<&[u32; NB]>::try_into(rk).unwrap())
         {
             sub_bytes(state);
@@ -87,110 +409,73 @@ impl<MODE> Aes128<MODE> {
 
         sub_bytes(state);
         shift_rows(state);
-        add_round_key(state, self.round_keys[NR * NB..].try_into().unwrap());
-
-        output.copy_from_slice(state)
+        add_round_key(
+            state,
+            self.round_keys[self.rounds() * NB..].try_into().unwrap(),
+        );
     }
 }
 
-pub struct ECB;
-pub struct CBC;
-
-impl Aes128<ECB> {
-    pub fn decrypt(&self, input: &[u8], output: &mut [u8]) {
-        assert_eq!(
-            input.len(),
-            output.len(),
-            "input and output buffers must have the same length"
-        );
-        assert_eq!(
-            input.len() % (NB * NK),
-            0,
-            "buffer length must be a multiple of sixteen bytes"
-        );
-
-        let mut state = [0u8; 16];
-
-        for (in_block, out_block) in input
-            .chunks_exact(NB * NK)
-            .zip(output.chunks_exact_mut(NB * NK))
-        {
-            self.decrypt_block(in_block, out_block, &mut state);
+impl<K: KeySize> BlockCipher for AesCore<K> {
+    type Block = [u8; BLOCK_SIZE];
+
+    const BLOCK_SIZE: usize = BLOCK_SIZE;
+
+    fn decrypt_block(&self, state: &mut [u8; BLOCK_SIZE]) {
+        match self.backend {
+            Backend::Reference => self.decrypt_rounds(state, inv_sub_bytes),
+            Backend::TTable => ttable::decrypt_block(&self.inv_round_keys, self.rounds(), state),
+            Backend::Bitsliced => self.decrypt_rounds(state, bitsliced::inv_sub_bytes),
+            #[cfg(target_arch = "x86_64")]
+            // SAFETY: `with_backend` and `Backend::detect` check for AES-NI.
+            Backend::AesNi => unsafe {
+                aesni::decrypt_block(&self.inv_round_keys, self.rounds(), state)
+            },
+            #[cfg(not(target_arch = "x86_64"))]
+            Backend::AesNi => unreachable!("AES-NI is only available on x86_64"),
         }
     }
 
-    pub fn encrypt(&self, input: &[u8], output: &mut [u8]) {
-        assert_eq!(
-            input.len(),
-            output.len(),
-            "input and output buffers must have the same length"
-        );
-        assert_eq!(
-            input.len() % (NB * NK),
-            0,
-            "buffer length must be a multiple of sixteen bytes"
-        );
-
-        let mut state = [0u8; 16];
-
-        for (in_block, out_block) in input
-            .chunks_exact(NB * NK)
-            .zip(output.chunks_exact_mut(NB * NK))
-        {
-            self.encrypt_block(in_block, out_block, &mut state);
+    fn encrypt_block(&self, state: &mut [u8; BLOCK_SIZE]) {
+        match self.backend {
+            Backend::Reference => self.encrypt_rounds(state, sub_bytes),
+            Backend::TTable => ttable::encrypt_block(&self.round_keys, self.rounds(), state),
+            Backend::Bitsliced => self.encrypt_rounds(state, bitsliced::sub_bytes),
+            #[cfg(target_arch = "x86_64")]
+            // SAFETY: `with_backend` and `Backend::detect` check for AES-NI.
+            Backend::AesNi => unsafe {
+                aesni::encrypt_block(&self.round_keys, self.rounds(), state)
+            },
+            #[cfg(not(target_arch = "x86_64"))]
+            Backend::AesNi => unreachable!("AES-NI is only available on x86_64"),
         }
     }
-}
-
-impl Aes128<CBC> {
-    pub fn decrypt(&self, input: &[u8], output: &mut [u8], iv: &[u8; 16]) {
-        assert_eq!(
-            input.len(),
-            output.len(),
-            "input and output buffers must have the same length"
-        );
-        assert_eq!(
-            input.len() % (NB * NK),
-            0,
-            "buffer length must be a multiple of sixteen bytes"
-        );
 
-        let mut state = [0u8; 16];
-        let mut prev = iv as &[u8];
-
-        for (in_block, out_block) in input
-            .chunks_exact(NB * NK)
-            .zip(output.chunks_exact_mut(NB * NK))
-        {
-            self.decrypt_block(in_block, out_block, &mut state);
-            crate::xor::xor_in_place(out_block, prev);
-            prev = in_block;
+    fn decrypt_blocks(&self, blocks: &mut [[u8; BLOCK_SIZE]]) {
+        match self.backend {
+            #[cfg(target_arch = "x86_64")]
+            // SAFETY: `with_backend` and `Backend::detect` check for AES-NI.
+            Backend::AesNi => unsafe {
+                aesni::decrypt_blocks(&self.inv_round_keys, self.rounds(), blocks)
+            },
+            Backend::TTable => ttable::decrypt_blocks(&self.inv_round_keys, self.rounds(), blocks),
+            _ => blocks
+                .iter_mut()
+                .for_each(|block| self.decrypt_block(block)),
         }
     }
 
-    pub fn encrypt(&self, input: &[u8], output: &mut [u8], iv: [u8; NB * NK]) {
-        assert_eq!(
-            input.len(),
-            output.len(),
-            "input and output buffers must have the same length"
-        );
-        assert_eq!(
-            input.len() % (NB * NK),
-            0,
-            "buffer length must be a multiple of sixteen bytes"
-        );
-
-        let mut state = [0u8; 16];
-        let mut temp = iv.to_owned();
-        let prev = &mut temp;
-
-        for (in_block, out_block) in input
-            .chunks_exact(NB * NK)
-            .zip(output.chunks_exact_mut(NB * NK))
-        {
-            crate::xor::xor_in_place(prev, in_block);
-            self.encrypt_block(prev, out_block, &mut state);
-            prev.copy_from_slice(out_block);
+    fn encrypt_blocks(&self, blocks: &mut [[u8; BLOCK_SIZE]]) {
+        match self.backend {
+            #[cfg(target_arch = "x86_64")]
+            // SAFETY: `with_backend` and `Backend::detect` check for AES-NI.
+            Backend::AesNi => unsafe {
+                aesni::encrypt_blocks(&self.round_keys, self.rounds(), blocks)
+            },
+            Backend::TTable => ttable::encrypt_blocks(&self.round_keys, self.rounds(), blocks),
+            _ => blocks
+                .iter_mut()
+                .for_each(|block| self.encrypt_block(block)),
         }
     }
 }
@@ -256,39 +541,130 @@ fn inv_sub_bytes(state: &mut [u8; 16]) {
     }
 }
 
+/// The size of an AES block in bytes.
+pub const BLOCK_SIZE: usize = 4 * NB;
+
 const NB: usize = 4;
-const NR: usize = 10;
-const NK: usize = 4;
 
 pub(crate) const RCON: [u32; 10] = [0x01, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x80, 0x1b, 0x36];
 
 // We create the same key schedule for both decryption and encryption, instead
 // using different sequence of transformations for the actual cipher.
-pub(crate) fn create_round_keys(key: &[u8]) -> [u32; NB * (NR + 1)] {
-    debug_assert_eq!(key.len(), 4 * NK);
+//
+// The key length selects the variant: 16, 24 and 32 byte keys expand into the
+// schedules for AES-128, AES-192 and AES-256 respectively.
+pub(crate) fn create_round_keys(key: &[u8]) -> Vec<u32> {
+    expand_key(key, sub_word)
+}
+
+/// The key expansion with the given implementation of `SubWord`.
+fn expand_key(key: &[u8], sub_word: fn(u32) -> u32) -> Vec<u32> {
+    debug_assert!(matches!(key.len(), 16 | 24 | 32));
+
+    let nk = key.len() / 4;
+    let nr = nk + 6;
 
     let mut i = 0;
-    let mut round_keys = [0u32; NB * (NR + 1)];
+    let mut round_keys = vec![0u32; NB * (nr + 1)];
 
     // TODO: Make sure this compiles to a `memcpy()`
-    while i < NK {
+    while i < nk {
         round_keys[i] = u32::from_le_bytes(key[4 * i..4 * (i + 1)].try_into().unwrap());
         i += 1;
     }
 
-    while i < NB * (NR + 1) {
-        let mut temp = round_keys[i - 1];
-        if (i % NK) == 0 {
-            temp = sub_word(rot_word(temp)) ^ RCON[(i / NK) - 1];
-        }
-
-        round_keys[i] = round_keys[i - NK] ^ temp;
+    while i < NB * (nr + 1) {
+        round_keys[i] = round_keys[i - nk] ^ schedule_temp(round_keys[i - 1], i, nk, sub_word);
         i += 1;
     }
 
     round_keys
 }
 
+/// The word that is combined with `w[i - nk]` to give `w[i]` in the key
+/// schedule, computed from the previous word `w[i - 1]`.
+fn schedule_temp(prev: u32, i: usize, nk: usize, sub_word: fn(u32) -> u32) -> u32 {
+    if i.is_multiple_of(nk) {
+        sub_word(rot_word(prev)) ^ RCON[(i / nk) - 1]
+    } else if nk > 6 && (i % nk) == 4 {
+        sub_word(prev)
+    } else {
+        prev
+    }
+}
+
+/// Runs the key schedule backwards and returns the whole schedule.
+///
+/// `round_key` holds `K::NK` consecutive words of the schedule, starting at
+/// round key `round`. For AES-128 that is exactly one round key. AES-192 and
+/// AES-256 need the first half and all of the following round key
+/// respectively, as a single round key is shorter than the cipher key.
+pub fn invert_key_schedule<K: KeySize>(
+    round_key: &[u8],
+    round: usize,
+) -> Result<Vec<u32>, crate::Error> {
+    if round_key.len() != 4 * K::NK {
+        return Err(crate::Error::InvalidKeyLength {
+            len: round_key.len(),
+            expected: 4 * K::NK,
+        });
+    }
+
+    let total = NB * (K::NR + 1);
+    let max = (total - K::NK) / NB;
+    if round > max {
+        return Err(crate::Error::InvalidRound { round, max });
+    }
+
+    let start = NB * round;
+    let mut round_keys = vec![0u32; total];
+    for (word, bytes) in round_keys[start..]
+        .iter_mut()
+        .zip(round_key.chunks_exact(4))
+    {
+        *word = u32::from_le_bytes(bytes.try_into().unwrap());
+    }
+
+    // w[i - nk] = w[i] ^ temp(w[i - 1]), and both w[i] and w[i - 1] are known
+    // when going down from the top of the window.
+    for i in (K::NK..start + K::NK).rev() {
+        round_keys[i - K::NK] =
+            round_keys[i] ^ schedule_temp(round_keys[i - 1], i, K::NK, sub_word);
+    }
+
+    for i in start + K::NK..total {
+        round_keys[i] =
+            round_keys[i - K::NK] ^ schedule_temp(round_keys[i - 1], i, K::NK, sub_word);
+    }
+
+    Ok(round_keys)
+}
+
+/// Recovers the cipher key from part of the key schedule, see
+/// [`invert_key_schedule`].
+pub fn recover_key<K: KeySize>(round_key: &[u8], round: usize) -> Result<Vec<u8>, crate::Error> {
+    let round_keys = invert_key_schedule::<K>(round_key, round)?;
+
+    Ok(round_keys[..K::NK]
+        .iter()
+        .flat_map(|w| w.to_le_bytes())
+        .collect())
+}
+
+/// The same as [`create_round_keys`] using AES-NI, or `None` if the CPU does
+/// not support it.
+pub(crate) fn create_round_keys_aesni(key: &[u8]) -> Option<Vec<u32>> {
+    debug_assert!(matches!(key.len(), 16 | 24 | 32));
+
+    #[cfg(target_arch = "x86_64")]
+    if aesni::is_available() {
+        // SAFETY: We just checked for AES-NI.
+        return Some(unsafe { aesni::create_round_keys(key) });
+    }
+
+    None
+}
+
 pub(crate) fn sub_word(w: u32) -> u32 {
     u32::from_le_bytes(w.to_le_bytes().map(|b| SBOX[b as usize]))
 }
@@ -0,0 +1,182 @@
+//! AES using the AES-NI instructions of x86_64 processors.
+//!
+//! Every function here requires the `aes` CPU feature, which callers must
+//! check with [`is_available`] first.
+
+use std::arch::x86_64::*;
+
+use super::{NB, RCON};
+
+pub(super) fn is_available() -> bool {
+    is_x86_feature_detected!("aes")
+}
+
+/// Expands `key` into the same schedule as [`super::create_round_keys`], but
+/// with `aeskeygenassist` in place of the S-box lookups.
+///
+/// # Safety
+/// The CPU must support AES-NI.
+#[target_feature(enable = "aes")]
+pub(super) unsafe fn create_round_keys(key: &[u8]) -> Vec<u32> {
+    let nk = key.len() / 4;
+    let nr = nk + 6;
+    let mut round_keys = vec![0u32; NB * (nr + 1)];
+
+    for (word, bytes) in round_keys.iter_mut().zip(key.chunks_exact(4)) {
+        *word = u32::from_le_bytes(bytes.try_into().unwrap());
+    }
+
+    for i in nk..NB * (nr + 1) {
+        let mut temp = round_keys[i - 1];
+        if (i % nk) == 0 {
+            temp = rot_sub_word(temp) ^ RCON[(i / nk) - 1];
+        } else if nk > 6 && (i % nk) == 4 {
+            temp = sub_word(temp);
+        }
+
+        round_keys[i] = round_keys[i - nk] ^ temp;
+    }
+
+    round_keys
+}
+
+/// `aeskeygenassist` computes `SubWord` of its second word into the first,
+/// and `RotWord(SubWord(..)) ^ rcon` into the second. The round constant has
+/// to be an immediate, so it is left at 0 and applied by the caller instead.
+#[target_feature(enable = "aes")]
+unsafe fn sub_word(w: u32) -> u32 {
+    let assist = _mm_aeskeygenassist_si128::<0>(_mm_set_epi32(0, 0, w as i32, 0));
+    _mm_cvtsi128_si32(assist) as u32
+}
+
+#[target_feature(enable = "aes")]
+unsafe fn rot_sub_word(w: u32) -> u32 {
+    let assist = _mm_aeskeygenassist_si128::<0>(_mm_set_epi32(0, 0, w as i32, 0));
+    _mm_cvtsi128_si32(_mm_shuffle_epi32::<0b01_01_01_01>(assist)) as u32
+}
+
+/// # Safety
+/// The CPU must support AES-NI.
+#[target_feature(enable = "aes")]
+pub(super) unsafe fn encrypt_block(round_keys: &[u32], nr: usize, block: &mut [u8; 16]) {
+    let mut state = _mm_loadu_si128(block.as_ptr().cast());
+    state = _mm_xor_si128(state, round_key(round_keys, 0));
+
+    for round in 1..nr {
+        state = _mm_aesenc_si128(state, round_key(round_keys, round));
+    }
+
+    state = _mm_aesenclast_si128(state, round_key(round_keys, nr));
+    _mm_storeu_si128(block.as_mut_ptr().cast(), state);
+}
+
+/// Decrypts with the equivalent inverse cipher, like
+/// [`super::ttable::decrypt_block`], with the same round keys.
+///
+/// # Safety
+/// The CPU must support AES-NI.
+#[target_feature(enable = "aes")]
+pub(super) unsafe fn decrypt_block(round_keys: &[u32], nr: usize, block: &mut [u8; 16]) {
+    let mut state = _mm_loadu_si128(block.as_ptr().cast());
+    state = _mm_xor_si128(state, round_key(round_keys, nr));
+
+    for round in (1..nr).rev() {
+        state = _mm_aesdec_si128(state, round_key(round_keys, round));
+    }
+
+    state = _mm_aesdeclast_si128(state, round_key(round_keys, 0));
+    _mm_storeu_si128(block.as_mut_ptr().cast(), state);
+}
+
+/// Encrypts the blocks eight at a time, so that the pipelined `aesenc`
+/// instructions of different blocks overlap.
+///
+/// # Safety
+/// The CPU must support AES-NI.
+#[target_feature(enable = "aes")]
+pub(super) unsafe fn encrypt_blocks(round_keys: &[u32], nr: usize, blocks: &mut [[u8; 16]]) {
+    let mut groups = blocks.chunks_exact_mut(LANES);
+    for group in &mut groups {
+        let mut states = load_lanes(group);
+        let rk = round_key(round_keys, 0);
+        for state in &mut states {
+            *state = _mm_xor_si128(*state, rk);
+        }
+
+        for round in 1..nr {
+            let rk = round_key(round_keys, round);
+            for state in &mut states {
+                *state = _mm_aesenc_si128(*state, rk);
+            }
+        }
+
+        let rk = round_key(round_keys, nr);
+        for state in &mut states {
+            *state = _mm_aesenclast_si128(*state, rk);
+        }
+        store_lanes(&states, group);
+    }
+
+    for block in groups.into_remainder() {
+        encrypt_block(round_keys, nr, block);
+    }
+}
+
+/// The inverse of [`encrypt_blocks`], with the round keys of
+/// [`decrypt_block`].
+///
+/// # Safety
+/// The CPU must support AES-NI.
+#[target_feature(enable = "aes")]
+pub(super) unsafe fn decrypt_blocks(round_keys: &[u32], nr: usize, blocks: &mut [[u8; 16]]) {
+    let mut groups = blocks.chunks_exact_mut(LANES);
+    for group in &mut groups {
+        let mut states = load_lanes(group);
+        let rk = round_key(round_keys, nr);
+        for state in &mut states {
+            *state = _mm_xor_si128(*state, rk);
+        }
+
+        for round in (1..nr).rev() {
+            let rk = round_key(round_keys, round);
+            for state in &mut states {
+                *state = _mm_aesdec_si128(*state, rk);
+            }
+        }
+
+        let rk = round_key(round_keys, 0);
+        for state in &mut states {
+            *state = _mm_aesdeclast_si128(*state, rk);
+        }
+        store_lanes(&states, group);
+    }
+
+    for block in groups.into_remainder() {
+        decrypt_block(round_keys, nr, block);
+    }
+}
+
+const LANES: usize = 8;
+
+#[target_feature(enable = "aes")]
+unsafe fn load_lanes(group: &[[u8; 16]]) -> [__m128i; LANES] {
+    let mut states = [_mm_setzero_si128(); LANES];
+    for (state, block) in states.iter_mut().zip(group) {
+        *state = _mm_loadu_si128(block.as_ptr().cast());
+    }
+
+    states
+}
+
+#[target_feature(enable = "aes")]
+unsafe fn store_lanes(states: &[__m128i; LANES], group: &mut [[u8; 16]]) {
+    for (state, block) in states.iter().zip(group) {
+        _mm_storeu_si128(block.as_mut_ptr().cast(), *state);
+    }
+}
+
+#[target_feature(enable = "aes")]
+unsafe fn round_key(round_keys: &[u32], round: usize) -> __m128i {
+    let words = &round_keys[NB * round..NB * (round + 1)];
+    _mm_loadu_si128(words.as_ptr().cast())
+}
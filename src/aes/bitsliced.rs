@@ -0,0 +1,121 @@
+//! A bitsliced S-box, used by the otherwise byte-oriented reference rounds so
+//! that `SubBytes` needs no table lookups indexed by secret data.
+//!
+//! Only the S-box is bitsliced, and only across the 16 bytes of one block per
+//! call. Each `SubBytes` transposes the state into eight 16-bit planes, where
+//! plane `i` holds bit `i` of every byte, evaluates the S-box as a boolean
+//! circuit on all of them at once, an inversion in GF(2^8) followed by the
+//! affine transformation from FIPS-197 section 5.1.1, and transposes back.
+//! `ShiftRows`, `MixColumns` and `AddRoundKey` stay the byte-wise reference
+//! code. A fully bitsliced AES would instead keep many blocks in bit planes
+//! for the whole cipher.
+//!
+//! The key expansion uses the same circuit for `SubWord`, one word at a time.
+
+/// Bit `j` of `planes[i]` is bit `i` of byte `j`.
+type Planes = [u16; 8];
+
+pub(super) fn sub_bytes(state: &mut [u8; 16]) {
+    let x = transpose(state);
+    let s = affine(inverse(x), &[0, 4, 5, 6, 7], 0x63);
+    *state = untranspose(&s);
+}
+
+/// `SubWord` from the key expansion, on the first four bytes of a state.
+pub(super) fn sub_word(w: u32) -> u32 {
+    let mut state = [0u8; 16];
+    state[..4].copy_from_slice(&w.to_le_bytes());
+    sub_bytes(&mut state);
+
+    u32::from_le_bytes(state[..4].try_into().unwrap())
+}
+
+pub(super) fn inv_sub_bytes(state: &mut [u8; 16]) {
+    let x = transpose(state);
+    let s = inverse(affine(x, &[2, 5, 7], 0x05));
+    *state = untranspose(&s);
+}
+
+fn transpose(state: &[u8; 16]) -> Planes {
+    let mut planes = [0u16; 8];
+    for (i, plane) in planes.iter_mut().enumerate() {
+        for (j, &b) in state.iter().enumerate() {
+            *plane |= (((b >> i) & 1) as u16) << j;
+        }
+    }
+
+    planes
+}
+
+fn untranspose(planes: &Planes) -> [u8; 16] {
+    let mut state = [0u8; 16];
+    for (i, plane) in planes.iter().enumerate() {
+        for (j, b) in state.iter_mut().enumerate() {
+            *b |= (((plane >> j) & 1) as u8) << i;
+        }
+    }
+
+    state
+}
+
+/// Computes `x_{i+t} ^ ... ^ c_i` over all taps `t` for every bit `i`, with
+/// indices taken mod 8.
+fn affine(x: Planes, taps: &[usize], c: u8) -> Planes {
+    let mut out = [0u16; 8];
+    for (i, o) in out.iter_mut().enumerate() {
+        *o = taps.iter().fold(0, |acc, t| acc ^ x[(i + t) % 8]);
+        // Flip the plane if bit `i` of the constant is set.
+        *o ^= ((c >> i) & 1) as u16 * 0xffff;
+    }
+
+    out
+}
+
+/// Computes `x^254`, which is the multiplicative inverse for `x != 0` and maps
+/// 0 to 0.
+fn inverse(x: Planes) -> Planes {
+    let x2 = square(&x);
+    let x3 = mul(&x2, &x);
+    let x12 = square(&square(&x3));
+    let x15 = mul(&x12, &x3);
+    let x240 = square(&square(&square(&square(&x15))));
+    let x252 = mul(&x240, &x12);
+
+    mul(&x252, &x2)
+}
+
+/// Multiplies in GF(2^8) modulo `x^8 + x^4 + x^3 + x + 1`.
+fn mul(a: &Planes, b: &Planes) -> Planes {
+    let mut product = [0u16; 15];
+    for (i, &ai) in a.iter().enumerate() {
+        for (j, &bj) in b.iter().enumerate() {
+            product[i + j] ^= ai & bj;
+        }
+    }
+
+    reduce(product)
+}
+
+/// Squaring is linear in characteristic 2, it only spreads out the bits.
+fn square(a: &Planes) -> Planes {
+    let mut product = [0u16; 15];
+    for (i, &ai) in a.iter().enumerate() {
+        product[2 * i] = ai;
+    }
+
+    reduce(product)
+}
+
+fn reduce(mut product: [u16; 15]) -> Planes {
+    // Fold the high terms back in with x^8 = x^4 + x^3 + x + 1, starting at the
+    // top as the folding can produce new high terms.
+    for k in (8..15).rev() {
+        let high = product[k];
+        product[k - 8] ^= high;
+        product[k - 7] ^= high;
+        product[k - 5] ^= high;
+        product[k - 4] ^= high;
+    }
+
+    product[..8].try_into().unwrap()
+}
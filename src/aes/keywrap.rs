@@ -0,0 +1,151 @@
+//! AES Key Wrap (RFC 3394) and Key Wrap with Padding (RFC 5649).
+
+use crate::modes::{constant_time_eq, BlockCipher, Cipher};
+use crate::Error;
+
+/// AES Key Wrap, for keys that are a multiple of 8 bytes and at least 16
+/// bytes long.
+pub struct KW;
+/// AES Key Wrap with Padding, for keys of any non-zero length.
+pub struct KWP;
+
+const SEMIBLOCK: usize = 8;
+
+/// The initial value of RFC 3394.
+const IV: [u8; SEMIBLOCK] = [0xa6; SEMIBLOCK];
+/// The first half of the alternative initial value of RFC 5649, which is
+/// followed by the length of the key.
+const AIV_PREFIX: [u8; 4] = [0xa6, 0x59, 0x59, 0xa6];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum KeyWrapError {
+    #[error("Invalid length {0} for a key or wrapped key")]
+    InvalidLength(usize),
+    #[error("Integrity check of the unwrapped key failed")]
+    IntegrityCheckFailed,
+}
+
+impl<C: BlockCipher<Block = [u8; 16]>> Cipher<C, KW> {
+    pub fn wrap_key(&self, key: &[u8]) -> Result<Vec<u8>, Error> {
+        if key.len() < 2 * SEMIBLOCK || !key.len().is_multiple_of(SEMIBLOCK) {
+            return Err(KeyWrapError::InvalidLength(key.len()).into());
+        }
+
+        let mut wrapped = [&IV, key].concat();
+        wrap(self.cipher(), &mut wrapped);
+
+        Ok(wrapped)
+    }
+
+    /// Unwraps `wrapped`, or fails with [`KeyWrapError::IntegrityCheckFailed`]
+    /// if it was not wrapped with the same key.
+    pub fn unwrap_key(&self, wrapped: &[u8]) -> Result<Vec<u8>, Error> {
+        if wrapped.len() < 3 * SEMIBLOCK || !wrapped.len().is_multiple_of(SEMIBLOCK) {
+            return Err(KeyWrapError::InvalidLength(wrapped.len()).into());
+        }
+
+        let mut key = wrapped.to_vec();
+        unwrap(self.cipher(), &mut key);
+
+        if !constant_time_eq(&key[..SEMIBLOCK], &IV) {
+            return Err(KeyWrapError::IntegrityCheckFailed.into());
+        }
+
+        Ok(key.split_off(SEMIBLOCK))
+    }
+}
+
+impl<C: BlockCipher<Block = [u8; 16]>> Cipher<C, KWP> {
+    pub fn wrap_key(&self, key: &[u8]) -> Result<Vec<u8>, Error> {
+        let len = u32::try_from(key.len())
+            .ok()
+            .filter(|&len| len > 0)
+            .ok_or(KeyWrapError::InvalidLength(key.len()))?;
+
+        let mut wrapped = [&AIV_PREFIX[..], &len.to_be_bytes(), key].concat();
+        wrapped.resize(key.len().next_multiple_of(SEMIBLOCK) + SEMIBLOCK, 0);
+
+        if wrapped.len() == 2 * SEMIBLOCK {
+            // A single semiblock of key is encrypted together with the AIV.
+            let block = wrapped.as_mut_slice().try_into().unwrap();
+            self.cipher().encrypt_block(block);
+        } else {
+            wrap(self.cipher(), &mut wrapped);
+        }
+
+        Ok(wrapped)
+    }
+
+    /// Unwraps `wrapped`, or fails with [`KeyWrapError::IntegrityCheckFailed`]
+    /// if it was not wrapped with the same key.
+    pub fn unwrap_key(&self, wrapped: &[u8]) -> Result<Vec<u8>, Error> {
+        if wrapped.len() < 2 * SEMIBLOCK || !wrapped.len().is_multiple_of(SEMIBLOCK) {
+            return Err(KeyWrapError::InvalidLength(wrapped.len()).into());
+        }
+
+        let mut key = wrapped.to_vec();
+        if key.len() == 2 * SEMIBLOCK {
+            let block = key.as_mut_slice().try_into().unwrap();
+            self.cipher().decrypt_block(block);
+        } else {
+            unwrap(self.cipher(), &mut key);
+        }
+
+        let padded_len = key.len() - SEMIBLOCK;
+        let len = u32::from_be_bytes(key[4..SEMIBLOCK].try_into().unwrap()) as usize;
+
+        // Run every check before deciding, so a failure does not reveal which
+        // one it was.
+        let prefix_ok = constant_time_eq(&key[..4], &AIV_PREFIX);
+        let len_ok = len <= padded_len && len + SEMIBLOCK > padded_len;
+        let len = len.min(padded_len);
+        let padding_ok = key[SEMIBLOCK + len..].iter().fold(0, |acc, b| acc | b) == 0;
+
+        if !(prefix_ok & len_ok & padding_ok) {
+            return Err(KeyWrapError::IntegrityCheckFailed.into());
+        }
+
+        key.truncate(SEMIBLOCK + len);
+        Ok(key.split_off(SEMIBLOCK))
+    }
+}
+
+/// The wrapping function W, applied to the initial value followed by the key.
+fn wrap<C: BlockCipher<Block = [u8; 16]>>(cipher: &C, buf: &mut [u8]) {
+    let n = buf.len() / SEMIBLOCK - 1;
+
+    for j in 0..6 {
+        for i in 1..=n {
+            let mut block = [0u8; 16];
+            block[..SEMIBLOCK].copy_from_slice(&buf[..SEMIBLOCK]);
+            block[SEMIBLOCK..].copy_from_slice(&buf[i * SEMIBLOCK..(i + 1) * SEMIBLOCK]);
+            cipher.encrypt_block(&mut block);
+
+            let t = (n * j + i) as u64;
+            let a = u64::from_be_bytes(block[..SEMIBLOCK].try_into().unwrap()) ^ t;
+            buf[..SEMIBLOCK].copy_from_slice(&a.to_be_bytes());
+            buf[i * SEMIBLOCK..(i + 1) * SEMIBLOCK].copy_from_slice(&block[SEMIBLOCK..]);
+        }
+    }
+}
+
+/// The inverse of [`wrap`], which leaves the initial value to be checked by the
+/// caller in the first semiblock.
+fn unwrap<C: BlockCipher<Block = [u8; 16]>>(cipher: &C, buf: &mut [u8]) {
+    let n = buf.len() / SEMIBLOCK - 1;
+
+    for j in (0..6).rev() {
+        for i in (1..=n).rev() {
+            let t = (n * j + i) as u64;
+            let a = u64::from_be_bytes(buf[..SEMIBLOCK].try_into().unwrap()) ^ t;
+
+            let mut block = [0u8; 16];
+            block[..SEMIBLOCK].copy_from_slice(&a.to_be_bytes());
+            block[SEMIBLOCK..].copy_from_slice(&buf[i * SEMIBLOCK..(i + 1) * SEMIBLOCK]);
+            cipher.decrypt_block(&mut block);
+
+            buf[..SEMIBLOCK].copy_from_slice(&block[..SEMIBLOCK]);
+            buf[i * SEMIBLOCK..(i + 1) * SEMIBLOCK].copy_from_slice(&block[SEMIBLOCK..]);
+        }
+    }
+}
@@ -0,0 +1,181 @@
+//! Padding schemes that extend messages to a multiple of the block size.
+//!
+//! Every scheme except [`ZeroPadding`] always adds at least one byte, so that
+//! the padding can be removed unambiguously.
+
+use super::PaddingError;
+use crate::random::fill_random;
+
+pub trait Padding {
+    /// Appends the padding to `buf`.
+    ///
+    /// # Panics
+    /// The function panics if `block_size` is zero, or larger than 255 for the
+    /// schemes that store the padding length in a byte.
+    fn pad(buf: &mut Vec<u8>, block_size: usize);
+
+    /// Checks the padding at the end of `buf` and returns the length of the
+    /// data without it. `buf` has to be a multiple of the block size.
+    fn unpadded_len(buf: &[u8], block_size: usize) -> Result<usize, PaddingError>;
+
+    /// Strips the padding from `buf`.
+    ///
+    /// The buffer is left untouched if the padding is invalid.
+    fn unpad(buf: &mut Vec<u8>, block_size: usize) -> Result<(), PaddingError> {
+        let len = Self::unpadded_len(buf, block_size)?;
+        buf.truncate(len);
+
+        Ok(())
+    }
+}
+
+/// PKCS#7: `n` bytes of value `n`.
+pub struct Pkcs7;
+/// ANSI X9.23: zeros, followed by the number of padding bytes.
+pub struct AnsiX923;
+/// ISO/IEC 7816-4: a single `0x80` byte, followed by zeros.
+pub struct Iso7816;
+/// ISO 10126: random bytes, followed by the number of padding bytes.
+///
+/// Padding panics if the operating system cannot provide random bytes, see
+/// [`crate::random::fill_random`].
+pub struct Iso10126;
+/// Zeros up to the next multiple of the block size, and none for messages
+/// that are already aligned.
+///
+/// This cannot tell trailing zeros in the message apart from padding, so
+/// unpadding strips every zero from the end of the last block.
+pub struct ZeroPadding;
+
+impl Padding for Pkcs7 {
+    fn pad(buf: &mut Vec<u8>, block_size: usize) {
+        let padding = length_padding(buf, block_size);
+        buf.resize(buf.len() + padding as usize, padding);
+    }
+
+    fn unpadded_len(buf: &[u8], block_size: usize) -> Result<usize, PaddingError> {
+        let len = length_byte(buf, block_size)?;
+        check_fill(&buf[len..buf.len() - 1], buf[buf.len() - 1])?;
+
+        Ok(len)
+    }
+}
+
+impl Padding for AnsiX923 {
+    fn pad(buf: &mut Vec<u8>, block_size: usize) {
+        let padding = length_padding(buf, block_size);
+        buf.resize(buf.len() + padding as usize - 1, 0);
+        buf.push(padding);
+    }
+
+    fn unpadded_len(buf: &[u8], block_size: usize) -> Result<usize, PaddingError> {
+        let len = length_byte(buf, block_size)?;
+        check_fill(&buf[len..buf.len() - 1], 0)?;
+
+        Ok(len)
+    }
+}
+
+impl Padding for Iso7816 {
+    fn pad(buf: &mut Vec<u8>, block_size: usize) {
+        let padding = padding_len(buf, block_size);
+        buf.push(0x80);
+        buf.resize(buf.len() + padding - 1, 0);
+    }
+
+    fn unpadded_len(buf: &[u8], block_size: usize) -> Result<usize, PaddingError> {
+        check_aligned(buf, block_size)?;
+        if buf.is_empty() {
+            return Err(PaddingError::Empty);
+        }
+
+        // The marker has to be in the last block.
+        let start = buf.len().saturating_sub(block_size);
+        let len = buf[start..]
+            .iter()
+            .rposition(|&b| b != 0)
+            .map(|i| start + i)
+            .ok_or(PaddingError::InvalidPadByte(0))?;
+
+        match buf[len] {
+            0x80 => Ok(len),
+            b => Err(PaddingError::InvalidPadByte(b)),
+        }
+    }
+}
+
+impl Padding for Iso10126 {
+    fn pad(buf: &mut Vec<u8>, block_size: usize) {
+        let padding = length_padding(buf, block_size);
+        let start = buf.len();
+        buf.resize(start + padding as usize - 1, 0);
+        fill_random(&mut buf[start..]);
+        buf.push(padding);
+    }
+
+    fn unpadded_len(buf: &[u8], block_size: usize) -> Result<usize, PaddingError> {
+        // The fill is random, so only the length can be checked.
+        length_byte(buf, block_size)
+    }
+}
+
+impl Padding for ZeroPadding {
+    fn pad(buf: &mut Vec<u8>, block_size: usize) {
+        assert!(block_size > 0, "block size must not be zero");
+        buf.resize(buf.len().next_multiple_of(block_size), 0);
+    }
+
+    fn unpadded_len(buf: &[u8], block_size: usize) -> Result<usize, PaddingError> {
+        check_aligned(buf, block_size)?;
+
+        let start = buf.len().saturating_sub(block_size);
+        let zeros = buf[start..].iter().rev().take_while(|&&b| b == 0).count();
+
+        Ok(buf.len() - zeros)
+    }
+}
+
+/// How many bytes the schemes that always pad add to `buf`, between 1 and
+/// `block_size`.
+fn padding_len(buf: &[u8], block_size: usize) -> usize {
+    assert!(block_size > 0, "block size must not be zero");
+    block_size - buf.len() % block_size
+}
+
+/// Like [`padding_len`], for the schemes that store the length in a byte.
+fn length_padding(buf: &[u8], block_size: usize) -> u8 {
+    let padding = padding_len(buf, block_size);
+    u8::try_from(padding).expect("block size must be at most 255 bytes")
+}
+
+/// Reads the padding length from the last byte of `buf` and returns the
+/// length of the data before it.
+fn length_byte(buf: &[u8], block_size: usize) -> Result<usize, PaddingError> {
+    check_aligned(buf, block_size)?;
+    let &last = buf.last().ok_or(PaddingError::Empty)?;
+    let padding = last as usize;
+
+    if padding == 0 || padding > block_size || padding > buf.len() {
+        return Err(PaddingError::InvalidPadByte(last));
+    }
+
+    Ok(buf.len() - padding)
+}
+
+/// Padded data is always a multiple of the block size, so anything else is
+/// rejected before looking at the padding bytes.
+fn check_aligned(buf: &[u8], block_size: usize) -> Result<(), PaddingError> {
+    if block_size == 0 || !buf.len().is_multiple_of(block_size) {
+        return Err(PaddingError::NotBlockAligned);
+    }
+
+    Ok(())
+}
+
+fn check_fill(fill: &[u8], expected: u8) -> Result<(), PaddingError> {
+    if fill.iter().any(|&b| b != expected) {
+        return Err(PaddingError::InconsistentPadding);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,79 @@
+//! The Square (integral) attack on AES-128 reduced to 4 rounds.
+//!
+//! A Λ-set is 256 plaintexts which take every value in one byte and agree on
+//! all others. After three rounds, every byte of the state XORs to zero over
+//! the set. The fourth round has no `MixColumns`, so each ciphertext byte only
+//! depends on one state byte and one byte of the last round key. Guessing that
+//! key byte and undoing `SubBytes` must give back a zero sum, which only the
+//! right guess does reliably. Every byte is guessed on its own, so the whole
+//! round key falls out after a few Λ-sets, and inverting the key schedule gives
+//! the cipher key.
+
+use super::{recover_key, Aes128ECB, Key128, INV_SBOX};
+
+/// The number of rounds the attack works against.
+pub const ROUNDS: usize = 4;
+
+/// More Λ-sets than this without a unique key is treated as a failure, which
+/// should only happen when `encrypt` is not 4-round AES-128.
+const MAX_SETS: u8 = 16;
+
+/// Returns the 256 plaintexts of Λ-set number `set`, with byte 0 active.
+pub fn lambda_set(set: u8) -> Vec<[u8; 16]> {
+    (0..=255)
+        .map(|active| {
+            let mut block = [set; 16];
+            block[0] = active;
+            block
+        })
+        .collect()
+}
+
+/// Recovers the key of 4-round AES-128 from the encryption oracle `encrypt`,
+/// or returns `None` if no key fits.
+pub fn attack(mut encrypt: impl FnMut(&[u8; 16]) -> [u8; 16]) -> Option<Vec<u8>> {
+    // The key bytes that are still possible for each position.
+    let mut candidates = vec![vec![true; 256]; 16];
+
+    for set in 0..MAX_SETS {
+        let ciphertexts: Vec<_> = lambda_set(set).iter().map(&mut encrypt).collect();
+
+        for (pos, possible) in candidates.iter_mut().enumerate() {
+            for guess in 0..=255u8 {
+                let sum = ciphertexts
+                    .iter()
+                    .fold(0, |acc, c| acc ^ INV_SBOX[(c[pos] ^ guess) as usize]);
+                if sum != 0 {
+                    possible[guess as usize] = false;
+                }
+            }
+        }
+
+        let counts: Vec<_> = candidates
+            .iter()
+            .map(|p| p.iter().filter(|&&b| b).count())
+            .collect();
+        if counts.contains(&0) {
+            return None;
+        }
+        if counts.iter().all(|&n| n == 1) {
+            break;
+        }
+    }
+
+    let round_key: Vec<u8> = candidates
+        .iter()
+        .map(|p| p.iter().position(|&b| b).map(|b| b as u8))
+        .collect::<Option<_>>()?;
+    let key = recover_key::<Key128>(&round_key, ROUNDS).ok()?;
+
+    // Confirm the key against the oracle, in case candidates were left over.
+    let plaintext = [0x42; 16];
+    let mut expected = [0u8; 16];
+    Aes128ECB::new(&key)
+        .with_rounds(ROUNDS)
+        .encrypt(&plaintext, &mut expected)
+        .ok()?;
+
+    (encrypt(&plaintext) == expected).then_some(key)
+}
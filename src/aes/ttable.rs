@@ -0,0 +1,212 @@
+//! AES using precomputed 32-bit lookup tables, which fold `SubBytes`,
+//! `ShiftRows` and `MixColumns` into four table lookups per column.
+//!
+//! The tables are indexed with secret data, so this backend leaks the key
+//! through cache timing.
+
+use super::{inv_mix_word, xtime, INV_SBOX, NB, SBOX};
+
+/// `TE[i][x]` is the contribution of the S-box output of `x` in row `i` to
+/// the mixed column.
+static TE: [[u32; 256]; 4] = {
+    let mut table = [[0u32; 256]; 4];
+
+    let mut x = 0;
+    while x < 256 {
+        let s = SBOX[x] as u32;
+        let s2 = xtime(s);
+        let s3 = s2 ^ s;
+
+        let word = s2 | (s << 8) | (s << 16) | (s3 << 24);
+        table[0][x] = word;
+        table[1][x] = word.rotate_left(8);
+        table[2][x] = word.rotate_left(16);
+        table[3][x] = word.rotate_left(24);
+
+        x += 1;
+    }
+
+    table
+};
+
+/// The inverse of [`TE`], combining `InvSubBytes` and `InvMixColumns`.
+static TD: [[u32; 256]; 4] = {
+    let mut table = [[0u32; 256]; 4];
+
+    let mut x = 0;
+    while x < 256 {
+        let s = INV_SBOX[x] as u32;
+        let s2 = xtime(s);
+        let s4 = xtime(s2);
+        let s8 = xtime(s4);
+
+        let s9 = s8 ^ s;
+        let s11 = s8 ^ s2 ^ s;
+        let s13 = s8 ^ s4 ^ s;
+        let s14 = s8 ^ s4 ^ s2;
+
+        let word = s14 | (s9 << 8) | (s13 << 16) | (s11 << 24);
+        table[0][x] = word;
+        table[1][x] = word.rotate_left(8);
+        table[2][x] = word.rotate_left(16);
+        table[3][x] = word.rotate_left(24);
+
+        x += 1;
+    }
+
+    table
+};
+
+/// Returns the round keys for the equivalent inverse cipher, which applies
+/// `InvMixColumns` to every round key except the first and the last.
+pub(super) fn decryption_round_keys(round_keys: &[u32]) -> Vec<u32> {
+    let last = round_keys.len() - NB;
+
+    round_keys
+        .iter()
+        .enumerate()
+        .map(|(i, &w)| {
+            if i < NB || i >= last {
+                w
+            } else {
+                inv_mix_word(w)
+            }
+        })
+        .collect()
+}
+
+/// How many blocks [`encrypt_blocks`] and [`decrypt_blocks`] run in lockstep.
+const LANES: usize = 4;
+
+pub(super) fn encrypt_block(round_keys: &[u32], nr: usize, block: &mut [u8; 16]) {
+    encrypt_lanes(round_keys, nr, std::slice::from_mut(block));
+}
+
+/// Decrypts with the equivalent inverse cipher, `round_keys` must come from
+/// [`decryption_round_keys`].
+pub(super) fn decrypt_block(round_keys: &[u32], nr: usize, block: &mut [u8; 16]) {
+    decrypt_lanes(round_keys, nr, std::slice::from_mut(block));
+}
+
+/// Encrypts independent blocks, [`LANES`] at a time, so that the table
+/// lookups of one block can overlap with those of the others.
+pub(super) fn encrypt_blocks(round_keys: &[u32], nr: usize, blocks: &mut [[u8; 16]]) {
+    for lanes in blocks.chunks_mut(LANES) {
+        encrypt_lanes(round_keys, nr, lanes);
+    }
+}
+
+/// The inverse of [`encrypt_blocks`], see [`decrypt_block`] for the keys.
+pub(super) fn decrypt_blocks(round_keys: &[u32], nr: usize, blocks: &mut [[u8; 16]]) {
+    for lanes in blocks.chunks_mut(LANES) {
+        decrypt_lanes(round_keys, nr, lanes);
+    }
+}
+
+/// Encrypts up to [`LANES`] blocks, one round of all of them at a time.
+fn encrypt_lanes(round_keys: &[u32], nr: usize, blocks: &mut [[u8; 16]]) {
+    let mut states = [[0u32; NB]; LANES];
+    let states = &mut states[..blocks.len()];
+
+    for (state, block) in states.iter_mut().zip(blocks.iter()) {
+        *state = load(block);
+        xor_round_key(state, &round_keys[..NB]);
+    }
+
+    for round_key in round_keys.chunks_exact(NB).skip(1).take(nr - 1) {
+        for state in states.iter_mut() {
+            let mut next = [0u32; NB];
+            for (c, col) in next.iter_mut().enumerate() {
+                *col = TE[0][byte(state[c], 0)]
+                    ^ TE[1][byte(state[(c + 1) % NB], 1)]
+                    ^ TE[2][byte(state[(c + 2) % NB], 2)]
+                    ^ TE[3][byte(state[(c + 3) % NB], 3)]
+                    ^ round_key[c];
+            }
+
+            *state = next;
+        }
+    }
+
+    for (state, block) in states.iter().zip(blocks.iter_mut()) {
+        let mut next = [0u32; NB];
+        for (c, col) in next.iter_mut().enumerate() {
+            *col = u32::from_le_bytes([
+                SBOX[byte(state[c], 0)],
+                SBOX[byte(state[(c + 1) % NB], 1)],
+                SBOX[byte(state[(c + 2) % NB], 2)],
+                SBOX[byte(state[(c + 3) % NB], 3)],
+            ]);
+        }
+        xor_round_key(&mut next, &round_keys[nr * NB..]);
+
+        store(&next, block);
+    }
+}
+
+/// The inverse of [`encrypt_lanes`].
+fn decrypt_lanes(round_keys: &[u32], nr: usize, blocks: &mut [[u8; 16]]) {
+    let mut states = [[0u32; NB]; LANES];
+    let states = &mut states[..blocks.len()];
+
+    for (state, block) in states.iter_mut().zip(blocks.iter()) {
+        *state = load(block);
+        xor_round_key(state, &round_keys[nr * NB..]);
+    }
+
+    for round_key in round_keys.rchunks_exact(NB).skip(1).take(nr - 1) {
+        for state in states.iter_mut() {
+            let mut next = [0u32; NB];
+            for (c, col) in next.iter_mut().enumerate() {
+                *col = TD[0][byte(state[c], 0)]
+                    ^ TD[1][byte(state[(c + 3) % NB], 1)]
+                    ^ TD[2][byte(state[(c + 2) % NB], 2)]
+                    ^ TD[3][byte(state[(c + 1) % NB], 3)]
+                    ^ round_key[c];
+            }
+
+            *state = next;
+        }
+    }
+
+    for (state, block) in states.iter().zip(blocks.iter_mut()) {
+        let mut next = [0u32; NB];
+        for (c, col) in next.iter_mut().enumerate() {
+            *col = u32::from_le_bytes([
+                INV_SBOX[byte(state[c], 0)],
+                INV_SBOX[byte(state[(c + 3) % NB], 1)],
+                INV_SBOX[byte(state[(c + 2) % NB], 2)],
+                INV_SBOX[byte(state[(c + 1) % NB], 3)],
+            ]);
+        }
+        xor_round_key(&mut next, &round_keys[..NB]);
+
+        store(&next, block);
+    }
+}
+
+fn load(block: &[u8; 16]) -> [u32; NB] {
+    let mut state = [0u32; NB];
+    for (col, bytes) in state.iter_mut().zip(block.chunks_exact(4)) {
+        *col = u32::from_le_bytes(bytes.try_into().unwrap());
+    }
+
+    state
+}
+
+fn store(state: &[u32; NB], block: &mut [u8; 16]) {
+    for (col, bytes) in state.iter().zip(block.chunks_exact_mut(4)) {
+        bytes.copy_from_slice(&col.to_le_bytes());
+    }
+}
+
+fn xor_round_key(state: &mut [u32; NB], round_key: &[u32]) {
+    for (col, rk) in state.iter_mut().zip(round_key) {
+        *col ^= rk;
+    }
+}
+
+/// Returns row `row` of a column.
+const fn byte(col: u32, row: usize) -> usize {
+    ((col >> (8 * row)) & 0xff) as usize
+}
@@ -7,6 +7,7 @@ use base64::Engine;
 
 pub trait Base64 {
     fn to_base64(&self) -> String;
+    #[allow(clippy::wrong_self_convention)]
     fn from_base64(&self) -> Result<Vec<u8>, base64::DecodeError>;
 }
 
@@ -24,7 +24,7 @@ fn to_hex(input: &[u8]) -> String {
 pub fn from_hex(input: &str) -> Result<Vec<u8>, FromHexError> {
     let input = input.as_bytes();
 
-    if input.len() % 2 != 0 {
+    if !input.len().is_multiple_of(2) {
         return Err(FromHexError::OddLength);
     }
 
@@ -1,6 +1,10 @@
 pub mod aes;
 pub mod base64;
 pub mod hex;
+pub mod mac;
+pub mod modes;
+pub mod oracle;
+pub mod random;
 pub mod xor;
 
 pub use crate::base64::Base64;
@@ -15,4 +19,26 @@ pub enum Error {
     Base64Error(#[from] ::base64::DecodeError),
     #[error("IO error")]
     IoError(#[from] std::io::Error),
+    #[error("Invalid padding")]
+    PaddingError(#[from] aes::PaddingError),
+    #[error("Authentication failed")]
+    AuthenticationError(#[from] aes::AuthenticationError),
+    #[error("Key wrap error")]
+    KeyWrapError(#[from] aes::KeyWrapError),
+    #[error("Input and output buffers have different lengths ({input} and {output})")]
+    LengthMismatch { input: usize, output: usize },
+    #[error("Buffer length {len} is not a multiple of the block size {block_size}")]
+    NotBlockAligned { len: usize, block_size: usize },
+    #[error("Input of {len} bytes is shorter than the minimum of {min}")]
+    TooShort { len: usize, min: usize },
+    #[error("Input of {len} bytes is longer than the maximum of {max}")]
+    TooLong { len: usize, max: usize },
+    #[error("Invalid key length {len}, expected {expected} bytes")]
+    InvalidKeyLength { len: usize, expected: usize },
+    #[error("Round {round} is out of range, the last usable round is {max}")]
+    InvalidRound { round: usize, max: usize },
+    #[error("Invalid nonce length {len}")]
+    InvalidNonceLength { len: usize },
+    #[error("Invalid tag length {len}")]
+    InvalidTagLength { len: usize },
 }
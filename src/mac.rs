@@ -0,0 +1,122 @@
+//! Message authentication codes built from a block cipher.
+
+use crate::modes::{constant_time_eq, AuthenticationError, BlockCipher};
+use crate::xor::xor_in_place;
+use crate::Error;
+
+/// Raw CBC-MAC: the last block of the CBC encryption of the message.
+///
+/// This is only secure for messages of one fixed length, and the variable IV
+/// of [`CbcMac::mac_with_iv`] makes it weaker still. Use [`Cmac`] for anything
+/// but attacking it.
+pub struct CbcMac<C> {
+    cipher: C,
+}
+
+impl<C: BlockCipher> CbcMac<C> {
+    pub fn new(cipher: C) -> Self {
+        Self { cipher }
+    }
+
+    /// Returns the MAC of `message` with an all zero IV. `message` has to be a
+    /// multiple of the block size.
+    pub fn mac(&self, message: &[u8]) -> Result<C::Block, Error> {
+        self.mac_with_iv(message, &C::Block::default())
+    }
+
+    pub fn mac_with_iv(&self, message: &[u8], iv: &C::Block) -> Result<C::Block, Error> {
+        if !message.len().is_multiple_of(C::BLOCK_SIZE) {
+            return Err(Error::NotBlockAligned {
+                len: message.len(),
+                block_size: C::BLOCK_SIZE,
+            });
+        }
+
+        let mut state = *iv;
+        for chunk in message.chunks_exact(C::BLOCK_SIZE) {
+            xor_in_place(state.as_mut(), chunk);
+            self.cipher.encrypt_block(&mut state);
+        }
+
+        Ok(state)
+    }
+
+    /// Checks `tag` against the MAC of `message` in constant time.
+    pub fn verify(&self, message: &[u8], tag: &[u8]) -> Result<(), Error> {
+        self.verify_with_iv(message, &C::Block::default(), tag)
+    }
+
+    pub fn verify_with_iv(&self, message: &[u8], iv: &C::Block, tag: &[u8]) -> Result<(), Error> {
+        let expected = self.mac_with_iv(message, iv)?;
+        check_tag(expected.as_ref(), tag)
+    }
+}
+
+/// CMAC as specified in RFC 4493, for ciphers with 16 byte blocks.
+pub struct Cmac<C> {
+    cipher: C,
+    k1: [u8; 16],
+    k2: [u8; 16],
+}
+
+impl<C: BlockCipher<Block = [u8; 16]>> Cmac<C> {
+    pub fn new(cipher: C) -> Self {
+        let mut l = [0u8; 16];
+        cipher.encrypt_block(&mut l);
+
+        let k1 = double(&l);
+        let k2 = double(&k1);
+
+        Self { cipher, k1, k2 }
+    }
+
+    /// Returns the two subkeys for the last block, `K1` and `K2`.
+    pub fn subkeys(&self) -> (&[u8; 16], &[u8; 16]) {
+        (&self.k1, &self.k2)
+    }
+
+    pub fn mac(&self, message: &[u8]) -> [u8; 16] {
+        // The last block is kept back even if complete, as it gets a subkey.
+        let split = message.len().saturating_sub(1) / 16 * 16;
+        let (head, last) = message.split_at(split);
+
+        let mut state = [0u8; 16];
+        for chunk in head.chunks_exact(16) {
+            xor_in_place(&mut state, chunk);
+            self.cipher.encrypt_block(&mut state);
+        }
+
+        xor_in_place(&mut state[..last.len()], last);
+        if last.len() == 16 {
+            xor_in_place(&mut state, &self.k1);
+        } else {
+            state[last.len()] ^= 0x80;
+            xor_in_place(&mut state, &self.k2);
+        }
+        self.cipher.encrypt_block(&mut state);
+
+        state
+    }
+
+    /// Checks `tag` against the MAC of `message` in constant time.
+    pub fn verify(&self, message: &[u8], tag: &[u8]) -> Result<(), Error> {
+        check_tag(&self.mac(message), tag)
+    }
+}
+
+/// Multiplies by `x` in GF(2^128) with the polynomial `x^128 + x^7 + x^2 + x +
+/// 1`, like `xtime` does for the bytes in GF(2^8).
+pub(crate) fn double(block: &[u8; 16]) -> [u8; 16] {
+    let x = u128::from_be_bytes(*block);
+    let carry = (x >> 127).wrapping_neg();
+
+    ((x << 1) ^ (carry & 0x87)).to_be_bytes()
+}
+
+fn check_tag(expected: &[u8], tag: &[u8]) -> Result<(), Error> {
+    if !constant_time_eq(expected, tag) {
+        return Err(AuthenticationError.into());
+    }
+
+    Ok(())
+}
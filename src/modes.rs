@@ -0,0 +1,762 @@
+use std::marker::PhantomData;
+
+use crate::aes::{Padding, Pkcs7};
+use crate::{xor::xor_in_place, Error};
+
+mod cbc_cs;
+mod ccm;
+mod gcm;
+mod siv;
+mod stream;
+mod xts;
+
+pub use cbc_cs::{CiphertextStealing, CBCCS};
+pub use ccm::CCM;
+pub use gcm::{GHash, Gf128, GCM};
+pub use siv::Siv;
+pub use stream::{CtrState, Decryptor, Encryptor, StreamingMode};
+pub use xts::Xts;
+
+/// A keyed permutation on fixed size blocks.
+///
+/// Implementing this is enough to use a cipher with every mode of operation in
+/// this module.
+pub trait BlockCipher {
+    /// A buffer holding exactly one block, such as `[u8; 16]`.
+    type Block: AsRef<[u8]> + AsMut<[u8]> + Default + Copy;
+
+    const BLOCK_SIZE: usize;
+
+    fn encrypt_block(&self, block: &mut Self::Block);
+    fn decrypt_block(&self, block: &mut Self::Block);
+
+    /// Encrypts independent blocks. Ciphers can override this to interleave
+    /// several blocks and keep more of the CPU busy.
+    fn encrypt_blocks(&self, blocks: &mut [Self::Block]) {
+        for block in blocks {
+            self.encrypt_block(block);
+        }
+    }
+
+    /// Decrypts independent blocks, see [`BlockCipher::encrypt_blocks`].
+    fn decrypt_blocks(&self, blocks: &mut [Self::Block]) {
+        for block in blocks {
+            self.decrypt_block(block);
+        }
+    }
+}
+
+impl<C: BlockCipher + ?Sized> BlockCipher for &C {
+    type Block = C::Block;
+
+    const BLOCK_SIZE: usize = C::BLOCK_SIZE;
+
+    fn encrypt_block(&self, block: &mut Self::Block) {
+        (**self).encrypt_block(block)
+    }
+
+    fn decrypt_block(&self, block: &mut Self::Block) {
+        (**self).decrypt_block(block)
+    }
+
+    fn encrypt_blocks(&self, blocks: &mut [Self::Block]) {
+        (**self).encrypt_blocks(blocks)
+    }
+
+    fn decrypt_blocks(&self, blocks: &mut [Self::Block]) {
+        (**self).decrypt_blocks(blocks)
+    }
+}
+
+/// How many blocks the parallelizable modes hand to the cipher at once.
+const PARALLEL_BLOCKS: usize = 8;
+
+/// Inputs shorter than this are not worth splitting across threads.
+const MIN_THREADED_LEN: usize = 64 * 1024;
+
+pub struct ECB;
+pub struct CBC;
+pub struct CTR;
+/// Full-block cipher feedback mode.
+pub struct CFB;
+/// Cipher feedback mode with an 8-bit segment size.
+pub struct CFB8;
+pub struct OFB;
+/// Propagating cipher block chaining mode.
+pub struct PCBC;
+
+/// A block cipher used in the mode of operation `MODE`.
+pub struct Cipher<C, MODE> {
+    cipher: C,
+    _marker: PhantomData<MODE>,
+}
+
+impl<C: BlockCipher, MODE> Cipher<C, MODE> {
+    pub fn from_cipher(cipher: C) -> Self {
+        Self {
+            cipher,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn cipher(&self) -> &C {
+        &self.cipher
+    }
+
+    pub fn into_cipher(self) -> C {
+        self.cipher
+    }
+
+    /// Borrows the cipher in another mode, for the modes built on top of it.
+    fn as_mode<M>(&self) -> Cipher<&C, M> {
+        Cipher::from_cipher(&self.cipher)
+    }
+}
+
+impl<C: BlockCipher> Cipher<C, ECB> {
+    pub fn decrypt(&self, input: &[u8], output: &mut [u8]) -> Result<(), Error> {
+        copy_to_output(input, output)?;
+        self.decrypt_in_place(output)
+    }
+
+    pub fn encrypt(&self, input: &[u8], output: &mut [u8]) -> Result<(), Error> {
+        copy_to_output(input, output)?;
+        self.encrypt_in_place(output)
+    }
+
+    pub fn decrypt_in_place(&self, buf: &mut [u8]) -> Result<(), Error> {
+        check_block_aligned::<C>(buf)?;
+        process_blocks::<C>(buf, |blocks| self.cipher.decrypt_blocks(blocks));
+
+        Ok(())
+    }
+
+    pub fn encrypt_in_place(&self, buf: &mut [u8]) -> Result<(), Error> {
+        check_block_aligned::<C>(buf)?;
+        process_blocks::<C>(buf, |blocks| self.cipher.encrypt_blocks(blocks));
+
+        Ok(())
+    }
+
+    /// Like [`Cipher::decrypt_in_place`], but large buffers are split across
+    /// all available cores.
+    pub fn decrypt_in_place_threaded(&self, buf: &mut [u8]) -> Result<(), Error>
+    where
+        C: Sync,
+    {
+        check_block_aligned::<C>(buf)?;
+        for_each_piece_threaded(buf, C::BLOCK_SIZE, |_, piece| {
+            process_blocks::<C>(piece, |blocks| self.cipher.decrypt_blocks(blocks))
+        });
+
+        Ok(())
+    }
+
+    /// Like [`Cipher::encrypt_in_place`], but large buffers are split across
+    /// all available cores.
+    pub fn encrypt_in_place_threaded(&self, buf: &mut [u8]) -> Result<(), Error>
+    where
+        C: Sync,
+    {
+        check_block_aligned::<C>(buf)?;
+        for_each_piece_threaded(buf, C::BLOCK_SIZE, |_, piece| {
+            process_blocks::<C>(piece, |blocks| self.cipher.encrypt_blocks(blocks))
+        });
+
+        Ok(())
+    }
+
+    /// Pads `plaintext` with PKCS#7 and returns its encryption.
+    pub fn encrypt_padded(&self, plaintext: &[u8]) -> Vec<u8> {
+        self.encrypt_padded_with::<Pkcs7>(plaintext)
+    }
+
+    /// Decrypts `ciphertext` and strips the PKCS#7 padding.
+    pub fn decrypt_padded(&self, ciphertext: &[u8]) -> Result<Vec<u8>, Error> {
+        self.decrypt_padded_with::<Pkcs7>(ciphertext)
+    }
+
+    /// Pads `buf` with PKCS#7 and encrypts it in place.
+    pub fn encrypt_padded_in_place(&self, buf: &mut Vec<u8>) {
+        self.encrypt_padded_in_place_with::<Pkcs7>(buf)
+    }
+
+    /// Decrypts `buf` in place and truncates it to remove the PKCS#7 padding.
+    pub fn decrypt_padded_in_place(&self, buf: &mut Vec<u8>) -> Result<(), Error> {
+        self.decrypt_padded_in_place_with::<Pkcs7>(buf)
+    }
+
+    /// Pads `plaintext` with `P` and returns its encryption.
+    pub fn encrypt_padded_with<P: Padding>(&self, plaintext: &[u8]) -> Vec<u8> {
+        let mut buf = plaintext.to_vec();
+        self.encrypt_padded_in_place_with::<P>(&mut buf);
+
+        buf
+    }
+
+    /// Decrypts `ciphertext` and strips the padding of scheme `P`.
+    pub fn decrypt_padded_with<P: Padding>(&self, ciphertext: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut buf = ciphertext.to_vec();
+        self.decrypt_padded_in_place_with::<P>(&mut buf)?;
+
+        Ok(buf)
+    }
+
+    pub fn encrypt_padded_in_place_with<P: Padding>(&self, buf: &mut Vec<u8>) {
+        P::pad(buf, C::BLOCK_SIZE);
+        self.encrypt_in_place(buf)
+            .expect("padded input is block aligned");
+    }
+
+    pub fn decrypt_padded_in_place_with<P: Padding>(&self, buf: &mut Vec<u8>) -> Result<(), Error> {
+        self.decrypt_in_place(buf)?;
+        P::unpad(buf, C::BLOCK_SIZE)?;
+
+        Ok(())
+    }
+}
+
+impl<C: BlockCipher> Cipher<C, CBC> {
+    pub fn decrypt(&self, input: &[u8], output: &mut [u8], iv: &C::Block) -> Result<(), Error> {
+        copy_to_output(input, output)?;
+        self.decrypt_in_place(output, iv)
+    }
+
+    pub fn encrypt(&self, input: &[u8], output: &mut [u8], iv: &C::Block) -> Result<(), Error> {
+        copy_to_output(input, output)?;
+        self.encrypt_in_place(output, iv)
+    }
+
+    pub fn decrypt_in_place(&self, buf: &mut [u8], iv: &C::Block) -> Result<(), Error> {
+        check_block_aligned::<C>(buf)?;
+
+        let mut prev = *iv;
+        self.decrypt_chained(buf, &mut prev);
+
+        Ok(())
+    }
+
+    pub fn encrypt_in_place(&self, buf: &mut [u8], iv: &C::Block) -> Result<(), Error> {
+        check_block_aligned::<C>(buf)?;
+
+        let mut prev = *iv;
+        self.encrypt_chained(buf, &mut prev);
+
+        Ok(())
+    }
+
+    /// Pads `plaintext` with PKCS#7 and returns its encryption.
+    pub fn encrypt_padded(&self, plaintext: &[u8], iv: &C::Block) -> Vec<u8> {
+        self.encrypt_padded_with::<Pkcs7>(plaintext, iv)
+    }
+
+    /// Decrypts `ciphertext` and strips the PKCS#7 padding.
+    pub fn decrypt_padded(&self, ciphertext: &[u8], iv: &C::Block) -> Result<Vec<u8>, Error> {
+        self.decrypt_padded_with::<Pkcs7>(ciphertext, iv)
+    }
+
+    /// Pads `buf` with PKCS#7 and encrypts it in place.
+    pub fn encrypt_padded_in_place(&self, buf: &mut Vec<u8>, iv: &C::Block) {
+        self.encrypt_padded_in_place_with::<Pkcs7>(buf, iv)
+    }
+
+    /// Decrypts `buf` in place and truncates it to remove the PKCS#7 padding.
+    pub fn decrypt_padded_in_place(&self, buf: &mut Vec<u8>, iv: &C::Block) -> Result<(), Error> {
+        self.decrypt_padded_in_place_with::<Pkcs7>(buf, iv)
+    }
+
+    /// Pads `plaintext` with `P` and returns its encryption.
+    pub fn encrypt_padded_with<P: Padding>(&self, plaintext: &[u8], iv: &C::Block) -> Vec<u8> {
+        let mut buf = plaintext.to_vec();
+        self.encrypt_padded_in_place_with::<P>(&mut buf, iv);
+
+        buf
+    }
+
+    /// Decrypts `ciphertext` and strips the padding of scheme `P`.
+    pub fn decrypt_padded_with<P: Padding>(
+        &self,
+        ciphertext: &[u8],
+        iv: &C::Block,
+    ) -> Result<Vec<u8>, Error> {
+        let mut buf = ciphertext.to_vec();
+        self.decrypt_padded_in_place_with::<P>(&mut buf, iv)?;
+
+        Ok(buf)
+    }
+
+    pub fn encrypt_padded_in_place_with<P: Padding>(&self, buf: &mut Vec<u8>, iv: &C::Block) {
+        P::pad(buf, C::BLOCK_SIZE);
+        self.encrypt_in_place(buf, iv)
+            .expect("padded input is block aligned");
+    }
+
+    pub fn decrypt_padded_in_place_with<P: Padding>(
+        &self,
+        buf: &mut Vec<u8>,
+        iv: &C::Block,
+    ) -> Result<(), Error> {
+        self.decrypt_in_place(buf, iv)?;
+        P::unpad(buf, C::BLOCK_SIZE)?;
+
+        Ok(())
+    }
+
+    /// Decrypts whole blocks chained onto `prev`, which is left holding the
+    /// chaining value for the next block.
+    fn decrypt_chained(&self, buf: &mut [u8], prev: &mut C::Block) {
+        for chunk in buf.chunks_exact_mut(C::BLOCK_SIZE) {
+            // The ciphertext block is overwritten below, but the next block
+            // still needs it.
+            let ciphertext = to_block::<C>(chunk);
+
+            let mut block = ciphertext;
+            self.cipher.decrypt_block(&mut block);
+            xor_in_place(block.as_mut(), prev.as_ref());
+            chunk.copy_from_slice(block.as_ref());
+
+            *prev = ciphertext;
+        }
+    }
+
+    /// Encrypts whole blocks chained onto `prev`, which is left holding the
+    /// chaining value for the next block.
+    fn encrypt_chained(&self, buf: &mut [u8], prev: &mut C::Block) {
+        for chunk in buf.chunks_exact_mut(C::BLOCK_SIZE) {
+            xor_in_place(prev.as_mut(), chunk);
+            self.cipher.encrypt_block(prev);
+            chunk.copy_from_slice(prev.as_ref());
+        }
+    }
+}
+
+impl<C: BlockCipher> Cipher<C, CTR> {
+    /// Decryption is the same operation as encryption in CTR mode.
+    pub fn decrypt(
+        &self,
+        input: &[u8],
+        output: &mut [u8],
+        iv: &C::Block,
+        layout: CounterLayout,
+    ) -> Result<(), Error> {
+        self.encrypt_at(input, output, iv, layout, 0)
+    }
+
+    pub fn encrypt(
+        &self,
+        input: &[u8],
+        output: &mut [u8],
+        iv: &C::Block,
+        layout: CounterLayout,
+    ) -> Result<(), Error> {
+        self.encrypt_at(input, output, iv, layout, 0)
+    }
+
+    /// Encrypts `input` as if it started at byte `offset` of the keystream.
+    ///
+    /// This allows editing any part of a CTR ciphertext without touching the
+    /// bytes before it.
+    pub fn encrypt_at(
+        &self,
+        input: &[u8],
+        output: &mut [u8],
+        iv: &C::Block,
+        layout: CounterLayout,
+        offset: u64,
+    ) -> Result<(), Error> {
+        copy_to_output(input, output)?;
+        self.encrypt_at_in_place(output, iv, layout, offset);
+
+        Ok(())
+    }
+
+    pub fn decrypt_in_place(&self, buf: &mut [u8], iv: &C::Block, layout: CounterLayout) {
+        self.encrypt_at_in_place(buf, iv, layout, 0)
+    }
+
+    pub fn encrypt_in_place(&self, buf: &mut [u8], iv: &C::Block, layout: CounterLayout) {
+        self.encrypt_at_in_place(buf, iv, layout, 0)
+    }
+
+    /// The in place version of [`Cipher::encrypt_at`].
+    pub fn encrypt_at_in_place(
+        &self,
+        buf: &mut [u8],
+        iv: &C::Block,
+        layout: CounterLayout,
+        offset: u64,
+    ) {
+        let block_size = C::BLOCK_SIZE as u64;
+        let mut block_idx = offset / block_size;
+        let mut skip = (offset % block_size) as usize;
+        let mut pos = 0;
+        let mut keystream = [C::Block::default(); PARALLEL_BLOCKS];
+
+        while pos < buf.len() {
+            let count = (skip + buf.len() - pos)
+                .div_ceil(C::BLOCK_SIZE)
+                .min(PARALLEL_BLOCKS);
+            for (i, block) in keystream[..count].iter_mut().enumerate() {
+                *block = layout.nth_block(iv, block_idx + i as u64);
+            }
+            self.cipher.encrypt_blocks(&mut keystream[..count]);
+
+            for block in &keystream[..count] {
+                let len = (C::BLOCK_SIZE - skip).min(buf.len() - pos);
+                xor_in_place(&mut buf[pos..pos + len], &block.as_ref()[skip..skip + len]);
+
+                pos += len;
+                skip = 0;
+            }
+            block_idx += count as u64;
+        }
+    }
+
+    /// Like [`Cipher::decrypt_in_place`], but large buffers are split across
+    /// all available cores.
+    pub fn decrypt_in_place_threaded(&self, buf: &mut [u8], iv: &C::Block, layout: CounterLayout)
+    where
+        C: Sync,
+        C::Block: Sync,
+    {
+        self.encrypt_in_place_threaded(buf, iv, layout)
+    }
+
+    /// Like [`Cipher::encrypt_in_place`], but large buffers are split across
+    /// all available cores.
+    pub fn encrypt_in_place_threaded(&self, buf: &mut [u8], iv: &C::Block, layout: CounterLayout)
+    where
+        C: Sync,
+        C::Block: Sync,
+    {
+        for_each_piece_threaded(buf, C::BLOCK_SIZE, |offset, piece| {
+            self.encrypt_at_in_place(piece, iv, layout, offset as u64)
+        });
+    }
+}
+
+impl<C: BlockCipher> Cipher<C, CFB> {
+    pub fn decrypt(&self, input: &[u8], output: &mut [u8], iv: &C::Block) -> Result<(), Error> {
+        copy_to_output(input, output)?;
+        self.decrypt_in_place(output, iv);
+
+        Ok(())
+    }
+
+    pub fn encrypt(&self, input: &[u8], output: &mut [u8], iv: &C::Block) -> Result<(), Error> {
+        copy_to_output(input, output)?;
+        self.encrypt_in_place(output, iv);
+
+        Ok(())
+    }
+
+    pub fn decrypt_in_place(&self, buf: &mut [u8], iv: &C::Block) {
+        let mut prev = *iv;
+        self.decrypt_chained(buf, &mut prev);
+    }
+
+    pub fn encrypt_in_place(&self, buf: &mut [u8], iv: &C::Block) {
+        let mut prev = *iv;
+        self.encrypt_chained(buf, &mut prev);
+    }
+
+    /// Decrypts `buf` chained onto the feedback block `prev`, which is updated
+    /// as long as `buf` is a multiple of the block size.
+    fn decrypt_chained(&self, buf: &mut [u8], prev: &mut C::Block) {
+        for chunk in buf.chunks_mut(C::BLOCK_SIZE) {
+            let len = chunk.len();
+            let mut keystream = *prev;
+            self.cipher.encrypt_block(&mut keystream);
+
+            prev.as_mut()[..len].copy_from_slice(chunk);
+            xor_in_place(chunk, &keystream.as_ref()[..len]);
+        }
+    }
+
+    /// Encrypts `buf` chained onto the feedback block `prev`, which is updated
+    /// as long as `buf` is a multiple of the block size.
+    fn encrypt_chained(&self, buf: &mut [u8], prev: &mut C::Block) {
+        for chunk in buf.chunks_mut(C::BLOCK_SIZE) {
+            let len = chunk.len();
+            self.cipher.encrypt_block(prev);
+            xor_in_place(&mut prev.as_mut()[..len], chunk);
+            chunk.copy_from_slice(&prev.as_ref()[..len]);
+        }
+    }
+}
+
+impl<C: BlockCipher> Cipher<C, CFB8> {
+    pub fn decrypt(&self, input: &[u8], output: &mut [u8], iv: &C::Block) -> Result<(), Error> {
+        copy_to_output(input, output)?;
+        self.decrypt_in_place(output, iv);
+
+        Ok(())
+    }
+
+    pub fn encrypt(&self, input: &[u8], output: &mut [u8], iv: &C::Block) -> Result<(), Error> {
+        copy_to_output(input, output)?;
+        self.encrypt_in_place(output, iv);
+
+        Ok(())
+    }
+
+    pub fn decrypt_in_place(&self, buf: &mut [u8], iv: &C::Block) {
+        let mut shift_reg = *iv;
+        self.process(buf, &mut shift_reg, false)
+    }
+
+    pub fn encrypt_in_place(&self, buf: &mut [u8], iv: &C::Block) {
+        let mut shift_reg = *iv;
+        self.process(buf, &mut shift_reg, true)
+    }
+
+    /// Runs the shift register `shift_reg` over `buf`, leaving it ready for
+    /// the next byte.
+    fn process(&self, buf: &mut [u8], shift_reg: &mut C::Block, encrypt: bool) {
+        for byte in buf {
+            let mut keystream = *shift_reg;
+            self.cipher.encrypt_block(&mut keystream);
+
+            let in_byte = *byte;
+            *byte ^= keystream.as_ref()[0];
+
+            // The ciphertext byte is always the one fed back.
+            let shift_reg = shift_reg.as_mut();
+            shift_reg.rotate_left(1);
+            shift_reg[C::BLOCK_SIZE - 1] = if encrypt { *byte } else { in_byte };
+        }
+    }
+}
+
+impl<C: BlockCipher> Cipher<C, OFB> {
+    /// Decryption is the same operation as encryption in OFB mode.
+    pub fn decrypt(&self, input: &[u8], output: &mut [u8], iv: &C::Block) -> Result<(), Error> {
+        self.encrypt(input, output, iv)
+    }
+
+    pub fn encrypt(&self, input: &[u8], output: &mut [u8], iv: &C::Block) -> Result<(), Error> {
+        copy_to_output(input, output)?;
+        self.encrypt_in_place(output, iv);
+
+        Ok(())
+    }
+
+    pub fn decrypt_in_place(&self, buf: &mut [u8], iv: &C::Block) {
+        self.encrypt_in_place(buf, iv)
+    }
+
+    pub fn encrypt_in_place(&self, buf: &mut [u8], iv: &C::Block) {
+        let mut keystream = *iv;
+        self.encrypt_chained(buf, &mut keystream);
+    }
+
+    /// Encrypts `buf` with the keystream following the block `keystream`,
+    /// which is updated as long as `buf` is a multiple of the block size.
+    fn encrypt_chained(&self, buf: &mut [u8], keystream: &mut C::Block) {
+        for chunk in buf.chunks_mut(C::BLOCK_SIZE) {
+            self.cipher.encrypt_block(keystream);
+            xor_in_place(chunk, &keystream.as_ref()[..chunk.len()]);
+        }
+    }
+}
+
+impl<C: BlockCipher> Cipher<C, PCBC> {
+    pub fn decrypt(&self, input: &[u8], output: &mut [u8], iv: &C::Block) -> Result<(), Error> {
+        copy_to_output(input, output)?;
+        self.decrypt_in_place(output, iv)
+    }
+
+    pub fn encrypt(&self, input: &[u8], output: &mut [u8], iv: &C::Block) -> Result<(), Error> {
+        copy_to_output(input, output)?;
+        self.encrypt_in_place(output, iv)
+    }
+
+    pub fn decrypt_in_place(&self, buf: &mut [u8], iv: &C::Block) -> Result<(), Error> {
+        check_block_aligned::<C>(buf)?;
+
+        let mut prev = *iv;
+        self.decrypt_chained(buf, &mut prev);
+
+        Ok(())
+    }
+
+    pub fn encrypt_in_place(&self, buf: &mut [u8], iv: &C::Block) -> Result<(), Error> {
+        check_block_aligned::<C>(buf)?;
+
+        let mut prev = *iv;
+        self.encrypt_chained(buf, &mut prev);
+
+        Ok(())
+    }
+
+    /// Decrypts whole blocks chained onto `prev`, which is left holding the
+    /// chaining value for the next block.
+    fn decrypt_chained(&self, buf: &mut [u8], prev: &mut C::Block) {
+        for chunk in buf.chunks_exact_mut(C::BLOCK_SIZE) {
+            let ciphertext = to_block::<C>(chunk);
+
+            let mut block = ciphertext;
+            self.cipher.decrypt_block(&mut block);
+            xor_in_place(block.as_mut(), prev.as_ref());
+            chunk.copy_from_slice(block.as_ref());
+
+            // The next block is chained on plaintext ^ ciphertext.
+            *prev = ciphertext;
+            xor_in_place(prev.as_mut(), chunk);
+        }
+    }
+
+    /// Encrypts whole blocks chained onto `prev`, which is left holding the
+    /// chaining value for the next block.
+    fn encrypt_chained(&self, buf: &mut [u8], prev: &mut C::Block) {
+        for chunk in buf.chunks_exact_mut(C::BLOCK_SIZE) {
+            let plaintext = to_block::<C>(chunk);
+
+            xor_in_place(prev.as_mut(), chunk);
+            self.cipher.encrypt_block(prev);
+            chunk.copy_from_slice(prev.as_ref());
+
+            // The next block is chained on plaintext ^ ciphertext.
+            xor_in_place(prev.as_mut(), plaintext.as_ref());
+        }
+    }
+}
+
+/// Where the counter lives inside the counter block in CTR mode, and how it
+/// is incremented.
+///
+/// The rest of the block is left as given in the IV and serves as the nonce.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CounterLayout {
+    /// The whole block is a single big-endian counter, as in NIST SP 800-38A.
+    /// For AES this is a 128-bit counter.
+    BigEndian128,
+    /// A nonce followed by a 32-bit big-endian counter, as used by GCM.
+    BigEndian32,
+    /// A nonce followed by a 64-bit little-endian block count, as used by the
+    /// cryptopals challenges.
+    LittleEndian64,
+}
+
+impl CounterLayout {
+    /// Returns the counter block used for the `n`th block of keystream.
+    pub(crate) fn nth_block<B: AsMut<[u8]> + Copy>(self, iv: &B, n: u64) -> B {
+        let mut block = *iv;
+        let bytes = block.as_mut();
+        let len = bytes.len();
+
+        match self {
+            CounterLayout::BigEndian128 => add_be(bytes, n),
+            CounterLayout::BigEndian32 => add_be(&mut bytes[len - 4..], n),
+            CounterLayout::LittleEndian64 => {
+                let ctr = &mut bytes[len - 8..];
+                let sum = u64::from_le_bytes((&*ctr).try_into().unwrap()).wrapping_add(n);
+                ctr.copy_from_slice(&sum.to_le_bytes());
+            }
+        }
+
+        block
+    }
+}
+
+/// Adds `n` to the big-endian number in `ctr`, wrapping around on overflow.
+fn add_be(ctr: &mut [u8], n: u64) {
+    let mut carry = n as u128;
+
+    for b in ctr.iter_mut().rev() {
+        if carry == 0 {
+            break;
+        }
+
+        let sum = *b as u128 + carry;
+        *b = sum as u8;
+        carry = sum >> 8;
+    }
+}
+
+fn to_block<C: BlockCipher>(bytes: &[u8]) -> C::Block {
+    let mut block = C::Block::default();
+    block.as_mut().copy_from_slice(bytes);
+
+    block
+}
+
+/// Hands the blocks in `buf` to `f` in batches of [`PARALLEL_BLOCKS`].
+fn process_blocks<C: BlockCipher>(buf: &mut [u8], f: impl Fn(&mut [C::Block])) {
+    let mut blocks = [C::Block::default(); PARALLEL_BLOCKS];
+
+    for chunk in buf.chunks_mut(PARALLEL_BLOCKS * C::BLOCK_SIZE) {
+        let count = chunk.len() / C::BLOCK_SIZE;
+        for (block, bytes) in blocks.iter_mut().zip(chunk.chunks_exact(C::BLOCK_SIZE)) {
+            block.as_mut().copy_from_slice(bytes);
+        }
+
+        f(&mut blocks[..count]);
+
+        for (block, bytes) in blocks.iter().zip(chunk.chunks_exact_mut(C::BLOCK_SIZE)) {
+            bytes.copy_from_slice(block.as_ref());
+        }
+    }
+}
+
+/// Splits `buf` into one piece per core and runs `f` on each piece, together
+/// with the offset of the piece in `buf`. Every piece except the last is a
+/// multiple of `align` long.
+fn for_each_piece_threaded(buf: &mut [u8], align: usize, f: impl Fn(usize, &mut [u8]) + Sync) {
+    let threads = std::thread::available_parallelism().map_or(1, usize::from);
+    if threads == 1 || buf.len() < MIN_THREADED_LEN {
+        return f(0, buf);
+    }
+
+    let piece_len = buf.len().div_ceil(threads).next_multiple_of(align);
+    std::thread::scope(|scope| {
+        for (i, piece) in buf.chunks_mut(piece_len).enumerate() {
+            let f = &f;
+            scope.spawn(move || f(i * piece_len, piece));
+        }
+    });
+}
+
+fn check_same_len(input: &[u8], output: &[u8]) -> Result<(), Error> {
+    if input.len() != output.len() {
+        return Err(Error::LengthMismatch {
+            input: input.len(),
+            output: output.len(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Copies `input` into `output` so that the in place version of a mode can
+/// run on it.
+fn copy_to_output(input: &[u8], output: &mut [u8]) -> Result<(), Error> {
+    check_same_len(input, output)?;
+    output.copy_from_slice(input);
+
+    Ok(())
+}
+
+fn check_block_aligned<C: BlockCipher>(buf: &[u8]) -> Result<(), Error> {
+    if !buf.len().is_multiple_of(C::BLOCK_SIZE) {
+        return Err(Error::NotBlockAligned {
+            len: buf.len(),
+            block_size: C::BLOCK_SIZE,
+        });
+    }
+
+    Ok(())
+}
+
+/// Compares two buffers without exiting early on the first mismatch.
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("Message authentication failed")]
+pub struct AuthenticationError;
@@ -0,0 +1,154 @@
+use super::{copy_to_output, to_block, BlockCipher, Cipher, CBC};
+use crate::xor::xor_in_place;
+use crate::Error;
+
+/// CBC with ciphertext stealing, from the addendum to NIST SP 800-38A.
+///
+/// Messages of any length from one block up are encrypted without padding,
+/// and the ciphertext is as long as the plaintext. The last, partial block is
+/// zero padded and encrypted as usual, and the bytes of the previous
+/// ciphertext block that the padding made redundant are dropped.
+pub struct CBCCS;
+
+/// Where the shortened ciphertext block ends up, see [`CBCCS`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CiphertextStealing {
+    /// The shortened block stays in front of the last block.
+    CS1,
+    /// The last two blocks are swapped, unless the message is block aligned.
+    /// This makes aligned messages the same as plain CBC.
+    CS2,
+    /// The last two blocks are always swapped, as done by Kerberos.
+    CS3,
+}
+
+impl<C: BlockCipher> Cipher<C, CBCCS> {
+    pub fn decrypt(
+        &self,
+        input: &[u8],
+        output: &mut [u8],
+        iv: &C::Block,
+        variant: CiphertextStealing,
+    ) -> Result<(), Error> {
+        copy_to_output(input, output)?;
+        self.decrypt_in_place(output, iv, variant)
+    }
+
+    pub fn encrypt(
+        &self,
+        input: &[u8],
+        output: &mut [u8],
+        iv: &C::Block,
+        variant: CiphertextStealing,
+    ) -> Result<(), Error> {
+        copy_to_output(input, output)?;
+        self.encrypt_in_place(output, iv, variant)
+    }
+
+    pub fn decrypt_in_place(
+        &self,
+        buf: &mut [u8],
+        iv: &C::Block,
+        variant: CiphertextStealing,
+    ) -> Result<(), Error> {
+        let (head_len, partial_len) = split::<C>(buf)?;
+        let cbc = self.as_mode::<CBC>();
+        let mut prev = *iv;
+
+        if buf.len() == C::BLOCK_SIZE {
+            // A single block is plain CBC.
+            cbc.decrypt_chained(buf, &mut prev);
+            return Ok(());
+        }
+
+        let (head, tail) = buf.split_at_mut(head_len);
+        if swaps::<C>(variant, partial_len) {
+            tail.rotate_left(C::BLOCK_SIZE);
+        }
+
+        // The chaining value for the second to last block, taken before
+        // `head` is decrypted.
+        let chain = match head.len().checked_sub(C::BLOCK_SIZE) {
+            Some(start) => to_block::<C>(&head[start..]),
+            None => *iv,
+        };
+        cbc.decrypt_chained(head, &mut prev);
+
+        // `tail` is the shortened second to last block followed by the last.
+        let (short, last) = tail.split_at_mut(partial_len);
+        let mut z = to_block::<C>(last);
+        self.cipher.decrypt_block(&mut z);
+
+        // The dropped bytes of the second to last block were XORed with zero
+        // padding, so they are still in `z`.
+        let mut second_last = z;
+        second_last.as_mut()[..partial_len].copy_from_slice(short);
+        xor_in_place(&mut z.as_mut()[..partial_len], short);
+
+        let mut prev = chain;
+        let mut block = second_last;
+        cbc.decrypt_chained(block.as_mut(), &mut prev);
+
+        tail[..C::BLOCK_SIZE].copy_from_slice(block.as_ref());
+        tail[C::BLOCK_SIZE..].copy_from_slice(&z.as_ref()[..partial_len]);
+
+        Ok(())
+    }
+
+    pub fn encrypt_in_place(
+        &self,
+        buf: &mut [u8],
+        iv: &C::Block,
+        variant: CiphertextStealing,
+    ) -> Result<(), Error> {
+        let (head_len, partial_len) = split::<C>(buf)?;
+        let cbc = self.as_mode::<CBC>();
+        let mut prev = *iv;
+
+        // Everything up to the partial block is plain CBC.
+        let full_len = head_len + C::BLOCK_SIZE;
+        cbc.encrypt_chained(&mut buf[..full_len], &mut prev);
+        if buf.len() == C::BLOCK_SIZE {
+            return Ok(());
+        }
+
+        // Encrypt the zero padded partial block, then drop the end of the
+        // second to last ciphertext block.
+        let mut last = C::Block::default();
+        last.as_mut()[..partial_len].copy_from_slice(&buf[full_len..]);
+        cbc.encrypt_chained(last.as_mut(), &mut prev);
+
+        let tail = &mut buf[head_len..];
+        tail[partial_len..].copy_from_slice(last.as_ref());
+        if swaps::<C>(variant, partial_len) {
+            tail.rotate_right(C::BLOCK_SIZE);
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the length of the blocks before the last two, and the length of the
+/// last block, which is between 1 and the block size.
+fn split<C: BlockCipher>(buf: &[u8]) -> Result<(usize, usize), Error> {
+    if buf.len() < C::BLOCK_SIZE {
+        return Err(Error::TooShort {
+            len: buf.len(),
+            min: C::BLOCK_SIZE,
+        });
+    }
+
+    let partial_len = (buf.len() - 1) % C::BLOCK_SIZE + 1;
+    let head_len = (buf.len() - partial_len).saturating_sub(C::BLOCK_SIZE);
+
+    Ok((head_len, partial_len))
+}
+
+/// Whether the last two blocks are swapped in the ciphertext.
+fn swaps<C: BlockCipher>(variant: CiphertextStealing, partial_len: usize) -> bool {
+    match variant {
+        CiphertextStealing::CS1 => false,
+        CiphertextStealing::CS2 => partial_len != C::BLOCK_SIZE,
+        CiphertextStealing::CS3 => true,
+    }
+}
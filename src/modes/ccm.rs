@@ -0,0 +1,168 @@
+use super::{copy_to_output, AuthenticationError, BlockCipher, Cipher, CounterLayout, CTR};
+use crate::mac::CbcMac;
+use crate::xor::xor_in_place;
+use crate::Error;
+
+const BLOCK_SIZE: usize = 16;
+
+/// Counter with CBC-MAC mode, as specified in NIST SP 800-38C.
+///
+/// The nonce is between 7 and 13 bytes long. Shorter nonces leave more room
+/// for the message length: a nonce of `n` bytes allows messages of fewer than
+/// `2^(8 * (15 - n))` bytes. The tag is 4, 6, 8, 10, 12, 14 or 16 bytes long.
+pub struct CCM;
+
+impl<C: BlockCipher<Block = [u8; BLOCK_SIZE]>> Cipher<C, CCM> {
+    /// Encrypts `plaintext` into `output` and writes the authentication tag
+    /// into `tag`, whose length selects the tag length.
+    pub fn seal(
+        &self,
+        nonce: &[u8],
+        aad: &[u8],
+        plaintext: &[u8],
+        output: &mut [u8],
+        tag: &mut [u8],
+    ) -> Result<(), Error> {
+        copy_to_output(plaintext, output)?;
+
+        self.seal_in_place(nonce, aad, output, tag)
+    }
+
+    /// Verifies `tag` and decrypts `ciphertext` into `output`, which is zeroed
+    /// if the tag does not match.
+    pub fn open(
+        &self,
+        nonce: &[u8],
+        aad: &[u8],
+        ciphertext: &[u8],
+        tag: &[u8],
+        output: &mut [u8],
+    ) -> Result<(), Error> {
+        copy_to_output(ciphertext, output)?;
+
+        self.open_in_place(nonce, aad, output, tag)
+    }
+
+    pub fn seal_in_place(
+        &self,
+        nonce: &[u8],
+        aad: &[u8],
+        buf: &mut [u8],
+        tag: &mut [u8],
+    ) -> Result<(), Error> {
+        check_params(nonce, tag.len(), buf.len())?;
+
+        let mac = self.mac(nonce, aad, buf, tag.len());
+        self.apply_keystream(nonce, buf);
+
+        tag.copy_from_slice(&mac[..tag.len()]);
+        Ok(())
+    }
+
+    /// The in place version of [`Cipher::open`].
+    pub fn open_in_place(
+        &self,
+        nonce: &[u8],
+        aad: &[u8],
+        buf: &mut [u8],
+        tag: &[u8],
+    ) -> Result<(), Error> {
+        check_params(nonce, tag.len(), buf.len())?;
+
+        self.apply_keystream(nonce, buf);
+        let mac = self.mac(nonce, aad, buf, tag.len());
+
+        if !super::constant_time_eq(tag, &mac[..tag.len()]) {
+            buf.fill(0);
+            return Err(AuthenticationError.into());
+        }
+
+        Ok(())
+    }
+
+    /// Returns the CBC-MAC of the plaintext, already encrypted with the first
+    /// counter block.
+    fn mac(&self, nonce: &[u8], aad: &[u8], plaintext: &[u8], tag_len: usize) -> [u8; BLOCK_SIZE] {
+        let q = 15 - nonce.len();
+
+        // B0 holds the flags, the nonce and the plaintext length. It is
+        // followed by the encoded AAD length and the AAD, then the plaintext,
+        // each zero padded to a multiple of the block size.
+        let mut b = vec![0u8; BLOCK_SIZE];
+        b[0] = (u8::from(!aad.is_empty()) << 6) | (((tag_len as u8 - 2) / 2) << 3) | (q as u8 - 1);
+        b[1..1 + nonce.len()].copy_from_slice(nonce);
+        b[1 + nonce.len()..].copy_from_slice(&(plaintext.len() as u128).to_be_bytes()[16 - q..]);
+
+        if !aad.is_empty() {
+            let len = aad.len() as u64;
+            if len < 0xff00 {
+                b.extend_from_slice(&(len as u16).to_be_bytes());
+            } else if len <= u32::MAX as u64 {
+                b.extend_from_slice(&[0xff, 0xfe]);
+                b.extend_from_slice(&(len as u32).to_be_bytes());
+            } else {
+                b.extend_from_slice(&[0xff, 0xff]);
+                b.extend_from_slice(&len.to_be_bytes());
+            }
+
+            b.extend_from_slice(aad);
+            zero_pad(&mut b);
+        }
+
+        b.extend_from_slice(plaintext);
+        zero_pad(&mut b);
+
+        let mut tag = CbcMac::new(&self.cipher)
+            .mac(&b)
+            .expect("formatted input is block aligned");
+        let mut s0 = counter_block(nonce);
+        self.cipher.encrypt_block(&mut s0);
+        xor_in_place(&mut tag, &s0);
+
+        tag
+    }
+
+    fn apply_keystream(&self, nonce: &[u8], buf: &mut [u8]) {
+        // Counter block i is the flags byte with the size of the counter
+        // field, the nonce, and i in the remaining 15 - nonce.len() bytes.
+        // A0 masks the tag, so the payload is encrypted from A1 on.
+        let a1 = CounterLayout::BigEndian128.nth_block(&counter_block(nonce), 1);
+        self.as_mode::<CTR>()
+            .encrypt_at_in_place(buf, &a1, CounterLayout::BigEndian128, 0);
+    }
+}
+
+fn zero_pad(buf: &mut Vec<u8>) {
+    buf.resize(buf.len().next_multiple_of(BLOCK_SIZE), 0);
+}
+
+/// Returns counter block 0, which holds the nonce and the length of the
+/// counter field.
+fn counter_block(nonce: &[u8]) -> [u8; BLOCK_SIZE] {
+    let mut ctr = [0u8; BLOCK_SIZE];
+    ctr[0] = 14 - nonce.len() as u8;
+    ctr[1..1 + nonce.len()].copy_from_slice(nonce);
+
+    ctr
+}
+
+fn check_params(nonce: &[u8], tag_len: usize, len: usize) -> Result<(), Error> {
+    if !(7..=13).contains(&nonce.len()) {
+        return Err(Error::InvalidNonceLength { len: nonce.len() });
+    }
+
+    if !(4..=16).contains(&tag_len) || !tag_len.is_multiple_of(2) {
+        return Err(Error::InvalidTagLength { len: tag_len });
+    }
+
+    // The length field has 15 - nonce.len() bytes.
+    let q = 15 - nonce.len();
+    if q < 8 && len as u64 >> (8 * q) != 0 {
+        return Err(Error::TooLong {
+            len,
+            max: (1 << (8 * q)) - 1,
+        });
+    }
+
+    Ok(())
+}
@@ -0,0 +1,293 @@
+use std::ops::{Mul, MulAssign};
+
+use super::{copy_to_output, AuthenticationError, BlockCipher, Cipher, CounterLayout, CTR};
+use crate::Error;
+
+const BLOCK_SIZE: usize = 16;
+
+/// The longest plaintext allowed by SP 800-38D, 2^39 - 256 bits. Past it the
+/// 32 bit counter wraps around to `J0`, whose keystream masks the tag.
+const MAX_PLAINTEXT_LEN: u64 = (1 << 36) - 32;
+
+/// The longest AAD allowed by SP 800-38D, 2^64 - 1 bits.
+const MAX_AAD_LEN: u64 = (1 << 61) - 1;
+
+/// Galois/Counter mode.
+pub struct GCM;
+
+impl<C: BlockCipher<Block = [u8; BLOCK_SIZE]>> Cipher<C, GCM> {
+    /// Encrypts `plaintext` into `output` and returns the authentication tag
+    /// covering both the ciphertext and `aad`.
+    ///
+    /// A nonce of 12 bytes is used directly, any other length is hashed first.
+    pub fn seal(
+        &self,
+        nonce: &[u8],
+        aad: &[u8],
+        plaintext: &[u8],
+        output: &mut [u8],
+    ) -> Result<[u8; BLOCK_SIZE], Error> {
+        copy_to_output(plaintext, output)?;
+
+        self.seal_in_place(nonce, aad, output)
+    }
+
+    /// Verifies the 16 byte `tag` and decrypts `ciphertext` into `output`,
+    /// which is zeroed if the tag does not match.
+    ///
+    /// Tags of any other length are rejected, use [`Cipher::open_truncated`]
+    /// to accept shorter ones.
+    pub fn open(
+        &self,
+        nonce: &[u8],
+        aad: &[u8],
+        ciphertext: &[u8],
+        tag: &[u8],
+        output: &mut [u8],
+    ) -> Result<(), Error> {
+        copy_to_output(ciphertext, output)?;
+
+        self.open_in_place(nonce, aad, output, tag)
+    }
+
+    /// Like [`Cipher::open`], but expects `tag` to hold the first `tag_len`
+    /// bytes of the tag.
+    ///
+    /// `tag_len` is chosen by the receiver and must be 12 to 16, or 4 or 8 for
+    /// the applications that SP 800-38D appendix C allows them in. A `tag` of
+    /// any other length fails authentication.
+    pub fn open_truncated(
+        &self,
+        nonce: &[u8],
+        aad: &[u8],
+        ciphertext: &[u8],
+        tag: &[u8],
+        tag_len: usize,
+        output: &mut [u8],
+    ) -> Result<(), Error> {
+        copy_to_output(ciphertext, output)?;
+
+        self.open_truncated_in_place(nonce, aad, output, tag, tag_len)
+    }
+
+    /// Encrypts `buf` in place and returns the authentication tag.
+    ///
+    /// Fails if the nonce is empty, or the plaintext or AAD is longer than
+    /// SP 800-38D allows.
+    pub fn seal_in_place(
+        &self,
+        nonce: &[u8],
+        aad: &[u8],
+        buf: &mut [u8],
+    ) -> Result<[u8; BLOCK_SIZE], Error> {
+        check_lengths(nonce, aad, buf)?;
+
+        let (h, j0) = self.init(nonce);
+        self.apply_keystream(&j0, buf);
+
+        Ok(self.tag(h, &j0, aad, buf))
+    }
+
+    /// The in place version of [`Cipher::open`]. `buf` is zeroed if the tag
+    /// does not match.
+    pub fn open_in_place(
+        &self,
+        nonce: &[u8],
+        aad: &[u8],
+        buf: &mut [u8],
+        tag: &[u8],
+    ) -> Result<(), Error> {
+        self.open_truncated_in_place(nonce, aad, buf, tag, BLOCK_SIZE)
+    }
+
+    /// The in place version of [`Cipher::open_truncated`].
+    pub fn open_truncated_in_place(
+        &self,
+        nonce: &[u8],
+        aad: &[u8],
+        buf: &mut [u8],
+        tag: &[u8],
+        tag_len: usize,
+    ) -> Result<(), Error> {
+        if !matches!(tag_len, 4 | 8 | 12..=BLOCK_SIZE) {
+            return Err(Error::InvalidTagLength { len: tag_len });
+        }
+
+        check_lengths(nonce, aad, buf)?;
+
+        let (h, j0) = self.init(nonce);
+        let expected = self.tag(h, &j0, aad, buf);
+
+        if tag.len() != tag_len || !super::constant_time_eq(tag, &expected[..tag_len]) {
+            buf.fill(0);
+            return Err(AuthenticationError.into());
+        }
+
+        self.apply_keystream(&j0, buf);
+
+        Ok(())
+    }
+
+    /// Returns the hash subkey `H` and the pre-counter block `J0`.
+    fn init(&self, nonce: &[u8]) -> (Gf128, [u8; BLOCK_SIZE]) {
+        let mut h = [0u8; BLOCK_SIZE];
+        self.cipher.encrypt_block(&mut h);
+        let h = Gf128::from_bytes(&h);
+
+        let j0 = if nonce.len() == 12 {
+            let mut j0 = [0u8; BLOCK_SIZE];
+            j0[..12].copy_from_slice(nonce);
+            j0[15] = 1;
+            j0
+        } else {
+            let mut ghash = GHash::new(h);
+            ghash.update(nonce);
+            ghash.update_lengths(0, nonce.len() as u64);
+            ghash.finalize()
+        };
+
+        (h, j0)
+    }
+
+    fn apply_keystream(&self, j0: &[u8; BLOCK_SIZE], buf: &mut [u8]) {
+        // J0 itself masks the tag, the payload starts at inc32(J0).
+        self.as_mode::<CTR>()
+            .encrypt_at_in_place(buf, &inc32(j0), CounterLayout::BigEndian32, 0);
+    }
+
+    fn tag(
+        &self,
+        h: Gf128,
+        j0: &[u8; BLOCK_SIZE],
+        aad: &[u8],
+        ciphertext: &[u8],
+    ) -> [u8; BLOCK_SIZE] {
+        let mut ghash = GHash::new(h);
+        ghash.update(aad);
+        ghash.update(ciphertext);
+        ghash.update_lengths(aad.len() as u64, ciphertext.len() as u64);
+        let mut tag = ghash.finalize();
+
+        let mut mask = *j0;
+        self.cipher.encrypt_block(&mut mask);
+        crate::xor::xor_in_place(&mut tag, &mask);
+
+        tag
+    }
+}
+
+fn inc32(block: &[u8; BLOCK_SIZE]) -> [u8; BLOCK_SIZE] {
+    CounterLayout::BigEndian32.nth_block(block, 1)
+}
+
+fn check_lengths(nonce: &[u8], aad: &[u8], text: &[u8]) -> Result<(), Error> {
+    if nonce.is_empty() {
+        return Err(Error::InvalidNonceLength { len: 0 });
+    }
+
+    if text.len() as u64 > MAX_PLAINTEXT_LEN {
+        return Err(Error::TooLong {
+            len: text.len(),
+            max: MAX_PLAINTEXT_LEN as usize,
+        });
+    }
+
+    if aad.len() as u64 > MAX_AAD_LEN {
+        return Err(Error::TooLong {
+            len: aad.len(),
+            max: MAX_AAD_LEN as usize,
+        });
+    }
+
+    Ok(())
+}
+
+/// An element of GF(2^128) with the bit order used by GCM.
+///
+/// The first bit of the 16 byte block is the coefficient of `x^0`, which puts
+/// it in the most significant bit of the inner `u128`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Gf128(pub u128);
+
+impl Gf128 {
+    /// The reduction polynomial `x^128 + x^7 + x^2 + x + 1` without the `x^128` term.
+    const R: u128 = 0xe1 << 120;
+
+    pub fn from_bytes(block: &[u8; BLOCK_SIZE]) -> Self {
+        Self(u128::from_be_bytes(*block))
+    }
+
+    pub fn to_bytes(self) -> [u8; BLOCK_SIZE] {
+        self.0.to_be_bytes()
+    }
+}
+
+impl Mul for Gf128 {
+    type Output = Self;
+
+    // Algorithm 1 of the GCM specification, without data dependent branches.
+    fn mul(self, rhs: Self) -> Self {
+        let mut z = 0u128;
+        let mut v = rhs.0;
+
+        for i in (0..128).rev() {
+            let bit = ((self.0 >> i) & 1).wrapping_neg();
+            z ^= v & bit;
+
+            let lsb = (v & 1).wrapping_neg();
+            v = (v >> 1) ^ (Self::R & lsb);
+        }
+
+        Self(z)
+    }
+}
+
+impl MulAssign for Gf128 {
+    fn mul_assign(&mut self, rhs: Self) {
+        *self = *self * rhs;
+    }
+}
+
+/// The universal hash used by GCM.
+///
+/// Each call to [`GHash::update`] is zero padded to a multiple of the block
+/// size.
+pub struct GHash {
+    h: Gf128,
+    y: Gf128,
+}
+
+impl GHash {
+    pub fn new(h: Gf128) -> Self {
+        Self {
+            h,
+            y: Gf128::default(),
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        for chunk in data.chunks(BLOCK_SIZE) {
+            let mut block = [0u8; BLOCK_SIZE];
+            block[..chunk.len()].copy_from_slice(chunk);
+
+            self.update_block(&block);
+        }
+    }
+
+    /// Absorbs the final length block, with lengths given in bytes.
+    pub fn update_lengths(&mut self, aad_len: u64, ciphertext_len: u64) {
+        let mut block = [0u8; BLOCK_SIZE];
+        block[..8].copy_from_slice(&(aad_len * 8).to_be_bytes());
+        block[8..].copy_from_slice(&(ciphertext_len * 8).to_be_bytes());
+
+        self.update_block(&block);
+    }
+
+    pub fn update_block(&mut self, block: &[u8; BLOCK_SIZE]) {
+        self.y = Gf128(self.y.0 ^ u128::from_be_bytes(*block)) * self.h;
+    }
+
+    pub fn finalize(self) -> [u8; BLOCK_SIZE] {
+        self.y.to_bytes()
+    }
+}
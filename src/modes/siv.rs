@@ -0,0 +1,112 @@
+use super::{constant_time_eq, AuthenticationError, BlockCipher, Cipher, CounterLayout, CTR};
+use crate::mac::{double, Cmac};
+use crate::xor::xor_in_place;
+use crate::Error;
+
+const BLOCK_SIZE: usize = 16;
+
+/// Synthetic initialization vector mode, as specified in RFC 5297.
+///
+/// The IV is a MAC over the associated data and the plaintext, so encryption
+/// is deterministic and reusing a nonce only reveals whether two messages are
+/// equal. A nonce, if any, is passed as one of the associated data components.
+pub struct Siv<C> {
+    mac: Cmac<C>,
+    ctr: Cipher<C, CTR>,
+}
+
+impl<C: BlockCipher<Block = [u8; BLOCK_SIZE]>> Siv<C> {
+    /// Uses `mac` for the synthetic IV and `ctr` for encryption. The two must
+    /// have independent keys.
+    pub fn from_ciphers(mac: C, ctr: C) -> Self {
+        Self {
+            mac: Cmac::new(mac),
+            ctr: Cipher::from_cipher(ctr),
+        }
+    }
+
+    /// Returns the synthetic IV followed by the encryption of `plaintext`.
+    pub fn seal(&self, associated_data: &[&[u8]], plaintext: &[u8]) -> Vec<u8> {
+        let mut output = vec![0u8; BLOCK_SIZE + plaintext.len()];
+        let (iv, ciphertext) = output.split_at_mut(BLOCK_SIZE);
+        ciphertext.copy_from_slice(plaintext);
+        iv.copy_from_slice(&self.seal_in_place(associated_data, ciphertext));
+
+        output
+    }
+
+    /// Verifies and decrypts the output of [`Siv::seal`].
+    pub fn open(&self, associated_data: &[&[u8]], ciphertext: &[u8]) -> Result<Vec<u8>, Error> {
+        if ciphertext.len() < BLOCK_SIZE {
+            return Err(AuthenticationError.into());
+        }
+
+        let (iv, ciphertext) = ciphertext.split_at(BLOCK_SIZE);
+        let mut output = ciphertext.to_vec();
+        self.open_in_place(associated_data, &mut output, iv)?;
+
+        Ok(output)
+    }
+
+    /// Encrypts `buf` in place and returns the synthetic IV.
+    pub fn seal_in_place(&self, associated_data: &[&[u8]], buf: &mut [u8]) -> [u8; BLOCK_SIZE] {
+        let iv = self.s2v(associated_data, buf);
+        self.ctr
+            .encrypt_in_place(buf, &counter(&iv), CounterLayout::BigEndian128);
+
+        iv
+    }
+
+    /// The in place version of [`Siv::open`]. `buf` is zeroed if `iv` does
+    /// not match.
+    pub fn open_in_place(
+        &self,
+        associated_data: &[&[u8]],
+        buf: &mut [u8],
+        iv: &[u8],
+    ) -> Result<(), Error> {
+        let ctr = counter(&iv.try_into().map_err(|_| AuthenticationError)?);
+        self.ctr
+            .decrypt_in_place(buf, &ctr, CounterLayout::BigEndian128);
+
+        let expected = self.s2v(associated_data, buf);
+        if !constant_time_eq(iv, &expected) {
+            buf.fill(0);
+            return Err(AuthenticationError.into());
+        }
+
+        Ok(())
+    }
+
+    /// Combines the associated data and the plaintext into the synthetic IV,
+    /// with CMAC as the pseudo-random function.
+    fn s2v(&self, associated_data: &[&[u8]], plaintext: &[u8]) -> [u8; BLOCK_SIZE] {
+        let mut d = self.mac.mac(&[0u8; BLOCK_SIZE]);
+        for component in associated_data {
+            d = double(&d);
+            xor_in_place(&mut d, &self.mac.mac(component));
+        }
+
+        let mut last = plaintext.to_vec();
+        if last.len() >= BLOCK_SIZE {
+            let start = last.len() - BLOCK_SIZE;
+            xor_in_place(&mut last[start..], &d);
+        } else {
+            last.push(0x80);
+            last.resize(BLOCK_SIZE, 0);
+            xor_in_place(&mut last, &double(&d));
+        }
+
+        self.mac.mac(&last)
+    }
+}
+
+/// The initial counter block is the IV with two bits cleared, which lets
+/// implementations use 32 or 64-bit additions for the counter.
+fn counter(iv: &[u8; BLOCK_SIZE]) -> [u8; BLOCK_SIZE] {
+    let mut ctr = *iv;
+    ctr[8] &= 0x7f;
+    ctr[12] &= 0x7f;
+
+    ctr
+}
@@ -0,0 +1,385 @@
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+
+use super::{BlockCipher, Cipher, CounterLayout, CBC, CFB, CFB8, CTR, ECB, OFB, PCBC};
+use crate::aes::{Padding, Pkcs7};
+use crate::Error;
+
+/// How much data is buffered at most before it is encrypted or decrypted.
+const BUF_SIZE: usize = 8 * 1024;
+
+/// A mode of operation that can carry its state from one piece of a message
+/// to the next.
+pub trait StreamingMode<C: BlockCipher>: Sized {
+    /// What the mode needs to remember between pieces, such as the last
+    /// ciphertext block. This starts out as the IV.
+    type State;
+
+    /// Whether messages are padded to a multiple of the block size.
+    const PADDED: bool;
+
+    /// Encrypts `buf` in place. `buf` is a multiple of the block size, except
+    /// for the last piece of a message in an unpadded mode.
+    fn encrypt_update(cipher: &Cipher<C, Self>, state: &mut Self::State, buf: &mut [u8]);
+
+    /// Decrypts `buf` in place, with the same length rules as
+    /// [`StreamingMode::encrypt_update`].
+    fn decrypt_update(cipher: &Cipher<C, Self>, state: &mut Self::State, buf: &mut [u8]);
+}
+
+impl<C: BlockCipher> StreamingMode<C> for ECB {
+    type State = ();
+
+    const PADDED: bool = true;
+
+    fn encrypt_update(cipher: &Cipher<C, Self>, _: &mut (), buf: &mut [u8]) {
+        cipher
+            .encrypt_in_place(buf)
+            .expect("buffer is block aligned")
+    }
+
+    fn decrypt_update(cipher: &Cipher<C, Self>, _: &mut (), buf: &mut [u8]) {
+        cipher
+            .decrypt_in_place(buf)
+            .expect("buffer is block aligned")
+    }
+}
+
+impl<C: BlockCipher> StreamingMode<C> for CBC {
+    type State = C::Block;
+
+    const PADDED: bool = true;
+
+    fn encrypt_update(cipher: &Cipher<C, Self>, prev: &mut C::Block, buf: &mut [u8]) {
+        cipher.encrypt_chained(buf, prev)
+    }
+
+    fn decrypt_update(cipher: &Cipher<C, Self>, prev: &mut C::Block, buf: &mut [u8]) {
+        cipher.decrypt_chained(buf, prev)
+    }
+}
+
+impl<C: BlockCipher> StreamingMode<C> for PCBC {
+    type State = C::Block;
+
+    const PADDED: bool = true;
+
+    fn encrypt_update(cipher: &Cipher<C, Self>, prev: &mut C::Block, buf: &mut [u8]) {
+        cipher.encrypt_chained(buf, prev)
+    }
+
+    fn decrypt_update(cipher: &Cipher<C, Self>, prev: &mut C::Block, buf: &mut [u8]) {
+        cipher.decrypt_chained(buf, prev)
+    }
+}
+
+/// The position in the keystream of a CTR mode message.
+pub struct CtrState<B> {
+    iv: B,
+    layout: CounterLayout,
+    offset: u64,
+}
+
+impl<B> CtrState<B> {
+    pub fn new(iv: B, layout: CounterLayout) -> Self {
+        Self {
+            iv,
+            layout,
+            offset: 0,
+        }
+    }
+}
+
+impl<C: BlockCipher> StreamingMode<C> for CTR {
+    type State = CtrState<C::Block>;
+
+    const PADDED: bool = false;
+
+    fn encrypt_update(cipher: &Cipher<C, Self>, state: &mut Self::State, buf: &mut [u8]) {
+        cipher.encrypt_at_in_place(buf, &state.iv, state.layout, state.offset);
+        state.offset += buf.len() as u64;
+    }
+
+    fn decrypt_update(cipher: &Cipher<C, Self>, state: &mut Self::State, buf: &mut [u8]) {
+        Self::encrypt_update(cipher, state, buf)
+    }
+}
+
+impl<C: BlockCipher> StreamingMode<C> for CFB {
+    type State = C::Block;
+
+    const PADDED: bool = false;
+
+    fn encrypt_update(cipher: &Cipher<C, Self>, prev: &mut C::Block, buf: &mut [u8]) {
+        cipher.encrypt_chained(buf, prev)
+    }
+
+    fn decrypt_update(cipher: &Cipher<C, Self>, prev: &mut C::Block, buf: &mut [u8]) {
+        cipher.decrypt_chained(buf, prev)
+    }
+}
+
+impl<C: BlockCipher> StreamingMode<C> for CFB8 {
+    type State = C::Block;
+
+    const PADDED: bool = false;
+
+    fn encrypt_update(cipher: &Cipher<C, Self>, shift_reg: &mut C::Block, buf: &mut [u8]) {
+        cipher.process(buf, shift_reg, true)
+    }
+
+    fn decrypt_update(cipher: &Cipher<C, Self>, shift_reg: &mut C::Block, buf: &mut [u8]) {
+        cipher.process(buf, shift_reg, false)
+    }
+}
+
+impl<C: BlockCipher> StreamingMode<C> for OFB {
+    type State = C::Block;
+
+    const PADDED: bool = false;
+
+    fn encrypt_update(cipher: &Cipher<C, Self>, keystream: &mut C::Block, buf: &mut [u8]) {
+        cipher.encrypt_chained(buf, keystream)
+    }
+
+    fn decrypt_update(cipher: &Cipher<C, Self>, keystream: &mut C::Block, buf: &mut [u8]) {
+        cipher.encrypt_chained(buf, keystream)
+    }
+}
+
+/// Encrypts everything written to it and writes the ciphertext to `W`.
+///
+/// Partial blocks are buffered until more data arrives. [`Encryptor::finish`]
+/// must be called at the end of the message to pad and write the last block,
+/// otherwise it is lost. Padded modes use PKCS#7 unless another scheme is
+/// picked with [`Encryptor::with_padding`].
+pub struct Encryptor<W: Write, C: BlockCipher, MODE: StreamingMode<C>, P: Padding = Pkcs7> {
+    writer: W,
+    cipher: Cipher<C, MODE>,
+    state: MODE::State,
+    // Plaintext of the trailing partial block.
+    pending: Vec<u8>,
+    // Ciphertext that has not been written yet, because the writer failed.
+    output: Vec<u8>,
+    _padding: PhantomData<P>,
+}
+
+impl<W: Write, C: BlockCipher, MODE: StreamingMode<C>> Encryptor<W, C, MODE> {
+    pub fn new(writer: W, cipher: Cipher<C, MODE>, state: MODE::State) -> Self {
+        Self {
+            writer,
+            cipher,
+            state,
+            pending: Vec::with_capacity(BUF_SIZE + C::BLOCK_SIZE),
+            output: Vec::with_capacity(BUF_SIZE + C::BLOCK_SIZE),
+            _padding: PhantomData,
+        }
+    }
+}
+
+impl<W: Write, C: BlockCipher, MODE: StreamingMode<C>, P: Padding> Encryptor<W, C, MODE, P> {
+    /// Pads the end of the message with `Q` instead.
+    pub fn with_padding<Q: Padding>(self) -> Encryptor<W, C, MODE, Q> {
+        Encryptor {
+            writer: self.writer,
+            cipher: self.cipher,
+            state: self.state,
+            pending: self.pending,
+            output: self.output,
+            _padding: PhantomData,
+        }
+    }
+
+    /// Encrypts the rest of the message and returns the inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.write_output()?;
+
+        if MODE::PADDED {
+            P::pad(&mut self.pending, C::BLOCK_SIZE);
+        }
+
+        MODE::encrypt_update(&self.cipher, &mut self.state, &mut self.pending);
+        self.writer.write_all(&self.pending)?;
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+
+    /// Writes the buffered ciphertext, keeping whatever the writer did not
+    /// accept so that it is neither lost nor encrypted twice.
+    fn write_output(&mut self) -> io::Result<()> {
+        while !self.output.is_empty() {
+            match self.writer.write(&self.output) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => {
+                    self.output.drain(..n);
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<W: Write, C: BlockCipher, MODE: StreamingMode<C>, P: Padding> Write
+    for Encryptor<W, C, MODE, P>
+{
+    /// Encrypts the complete blocks of `buf` and writes them out.
+    ///
+    /// If the inner writer fails after part of `buf` was encrypted, that part
+    /// is reported as written and its ciphertext stays buffered. The next
+    /// call retries the buffered ciphertext first and returns the error if
+    /// the writer still fails.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_output()?;
+
+        let mut consumed = 0;
+        for piece in buf.chunks(BUF_SIZE) {
+            self.pending.extend_from_slice(piece);
+            consumed += piece.len();
+
+            let len = self.pending.len() - self.pending.len() % C::BLOCK_SIZE;
+            self.output.extend(self.pending.drain(..len));
+            MODE::encrypt_update(&self.cipher, &mut self.state, &mut self.output);
+
+            if self.write_output().is_err() {
+                return Ok(consumed);
+            }
+        }
+
+        Ok(consumed)
+    }
+
+    /// Writes any buffered ciphertext and flushes the inner writer. A
+    /// trailing partial block stays buffered.
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_output()?;
+        self.writer.flush()
+    }
+}
+
+/// Reads ciphertext from `R` and decrypts it.
+///
+/// In padded modes the last block is held back until the end of the input,
+/// where the padding is checked and removed. PKCS#7 is expected unless
+/// another scheme is picked with [`Decryptor::with_padding`].
+pub struct Decryptor<R: Read, C: BlockCipher, MODE: StreamingMode<C>, P: Padding = Pkcs7> {
+    reader: R,
+    cipher: Cipher<C, MODE>,
+    state: MODE::State,
+    // Ciphertext that has not been decrypted yet.
+    pending: Vec<u8>,
+    // Decrypted data from `pos` on has not been returned yet.
+    plaintext: Vec<u8>,
+    pos: usize,
+    done: bool,
+    _padding: PhantomData<P>,
+}
+
+impl<R: Read, C: BlockCipher, MODE: StreamingMode<C>> Decryptor<R, C, MODE> {
+    pub fn new(reader: R, cipher: Cipher<C, MODE>, state: MODE::State) -> Self {
+        Self {
+            reader,
+            cipher,
+            state,
+            pending: Vec::with_capacity(BUF_SIZE + C::BLOCK_SIZE),
+            plaintext: Vec::with_capacity(BUF_SIZE + C::BLOCK_SIZE),
+            pos: 0,
+            done: false,
+            _padding: PhantomData,
+        }
+    }
+}
+
+impl<R: Read, C: BlockCipher, MODE: StreamingMode<C>, P: Padding> Decryptor<R, C, MODE, P> {
+    /// Expects the message to be padded with `Q` instead.
+    pub fn with_padding<Q: Padding>(self) -> Decryptor<R, C, MODE, Q> {
+        Decryptor {
+            reader: self.reader,
+            cipher: self.cipher,
+            state: self.state,
+            pending: self.pending,
+            plaintext: self.plaintext,
+            pos: self.pos,
+            done: self.done,
+            _padding: PhantomData,
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn fill(&mut self) -> io::Result<()> {
+        let mut chunk = [0u8; BUF_SIZE];
+        let n = self.reader.read(&mut chunk)?;
+
+        self.plaintext.clear();
+        self.pos = 0;
+
+        if n == 0 {
+            self.done = true;
+            return self.finish();
+        }
+
+        self.pending.extend_from_slice(&chunk[..n]);
+
+        let mut len = self.pending.len() - self.pending.len() % C::BLOCK_SIZE;
+        if MODE::PADDED && len == self.pending.len() {
+            // This could be the block with the padding.
+            len = len.saturating_sub(C::BLOCK_SIZE);
+        }
+
+        self.plaintext.extend(self.pending.drain(..len));
+        MODE::decrypt_update(&self.cipher, &mut self.state, &mut self.plaintext);
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        let mut last = std::mem::take(&mut self.pending);
+
+        if MODE::PADDED && !last.len().is_multiple_of(C::BLOCK_SIZE) {
+            return Err(invalid_data(Error::NotBlockAligned {
+                len: last.len(),
+                block_size: C::BLOCK_SIZE,
+            }));
+        }
+
+        MODE::decrypt_update(&self.cipher, &mut self.state, &mut last);
+
+        if MODE::PADDED {
+            P::unpad(&mut last, C::BLOCK_SIZE).map_err(|e| invalid_data(e.into()))?;
+        }
+
+        self.plaintext = last;
+
+        Ok(())
+    }
+}
+
+impl<R: Read, C: BlockCipher, MODE: StreamingMode<C>, P: Padding> Read
+    for Decryptor<R, C, MODE, P>
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.plaintext.len() {
+            if self.done {
+                return Ok(0);
+            }
+
+            self.fill()?;
+        }
+
+        let n = buf.len().min(self.plaintext.len() - self.pos);
+        buf[..n].copy_from_slice(&self.plaintext[self.pos..self.pos + n]);
+        self.pos += n;
+
+        Ok(n)
+    }
+}
+
+fn invalid_data(err: Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
@@ -0,0 +1,131 @@
+use super::{copy_to_output, BlockCipher};
+use crate::xor::xor_in_place;
+use crate::Error;
+
+const BLOCK_SIZE: usize = 16;
+
+/// XEX-based tweaked codebook mode with ciphertext stealing, as specified in
+/// IEEE 1619 for disk encryption.
+///
+/// Every sector is encrypted independently under a tweak derived from its
+/// number. Unlike CBC, equal blocks at different positions encrypt
+/// differently, any block can be decrypted on its own and the ciphertext is
+/// exactly as long as the plaintext.
+pub struct Xts<C> {
+    data: C,
+    tweak: C,
+}
+
+impl<C: BlockCipher<Block = [u8; BLOCK_SIZE]>> Xts<C> {
+    /// Uses `data` to encrypt the sectors and `tweak` to encrypt the sector
+    /// numbers. The two must have independent keys.
+    pub fn from_ciphers(data: C, tweak: C) -> Self {
+        Self { data, tweak }
+    }
+
+    pub fn decrypt(&self, input: &[u8], output: &mut [u8], sector: u128) -> Result<(), Error> {
+        copy_to_output(input, output)?;
+        self.decrypt_in_place(output, sector)
+    }
+
+    pub fn encrypt(&self, input: &[u8], output: &mut [u8], sector: u128) -> Result<(), Error> {
+        copy_to_output(input, output)?;
+        self.encrypt_in_place(output, sector)
+    }
+
+    /// Decrypts the sector `sector` in place. Sectors can have any length of
+    /// at least one block.
+    pub fn decrypt_in_place(&self, buf: &mut [u8], sector: u128) -> Result<(), Error> {
+        check_len(buf)?;
+
+        let mut tweak = self.initial_tweak(sector);
+        let (full, rem) = (buf.len() / BLOCK_SIZE, buf.len() % BLOCK_SIZE);
+        // With stealing, the last full block needs to be handled separately.
+        let plain_blocks = if rem == 0 { full } else { full - 1 };
+
+        for chunk in buf[..plain_blocks * BLOCK_SIZE].chunks_exact_mut(BLOCK_SIZE) {
+            self.xex(chunk, &tweak, false);
+            tweak = double(tweak);
+        }
+
+        if rem != 0 {
+            // The last full ciphertext block used the tweak after its own.
+            let (last_full, partial) = buf[plain_blocks * BLOCK_SIZE..].split_at_mut(BLOCK_SIZE);
+            self.xex(last_full, &double(tweak), false);
+
+            // Now `last_full` holds the final partial plaintext followed by the
+            // stolen ciphertext.
+            last_full[..rem].swap_with_slice(partial);
+            self.xex(last_full, &tweak, false);
+        }
+
+        Ok(())
+    }
+
+    /// Encrypts the sector `sector` in place. Sectors can have any length of
+    /// at least one block.
+    pub fn encrypt_in_place(&self, buf: &mut [u8], sector: u128) -> Result<(), Error> {
+        check_len(buf)?;
+
+        let mut tweak = self.initial_tweak(sector);
+        let rem = buf.len() % BLOCK_SIZE;
+
+        for chunk in buf.chunks_exact_mut(BLOCK_SIZE) {
+            self.xex(chunk, &tweak, true);
+            tweak = double(tweak);
+        }
+
+        if rem != 0 {
+            // Steal the end of the last full ciphertext block to pad the
+            // partial block, and put the partial ciphertext in its place.
+            let start = buf.len() - rem - BLOCK_SIZE;
+            let (last_full, partial) = buf[start..].split_at_mut(BLOCK_SIZE);
+            last_full[..rem].swap_with_slice(partial);
+            self.xex(last_full, &tweak, true);
+        }
+
+        Ok(())
+    }
+
+    /// Encrypts the sector number, stored as a 128-bit little-endian number.
+    fn initial_tweak(&self, sector: u128) -> u128 {
+        let mut tweak = sector.to_le_bytes();
+        self.tweak.encrypt_block(&mut tweak);
+
+        u128::from_le_bytes(tweak)
+    }
+
+    fn xex(&self, chunk: &mut [u8], tweak: &u128, encrypt: bool) {
+        let tweak = tweak.to_le_bytes();
+        let mut block: [u8; BLOCK_SIZE] = chunk.try_into().unwrap();
+
+        xor_in_place(&mut block, &tweak);
+        if encrypt {
+            self.data.encrypt_block(&mut block);
+        } else {
+            self.data.decrypt_block(&mut block);
+        }
+        xor_in_place(&mut block, &tweak);
+
+        chunk.copy_from_slice(&block);
+    }
+}
+
+/// Multiplies the tweak by `x` in GF(2^128). XTS stores field elements in
+/// little-endian order, unlike GCM.
+fn double(tweak: u128) -> u128 {
+    let carry = (tweak >> 127).wrapping_neg();
+
+    (tweak << 1) ^ (carry & 0x87)
+}
+
+fn check_len(buf: &[u8]) -> Result<(), Error> {
+    if buf.len() < BLOCK_SIZE {
+        return Err(Error::TooShort {
+            len: buf.len(),
+            min: BLOCK_SIZE,
+        });
+    }
+
+    Ok(())
+}
@@ -0,0 +1,93 @@
+//! The ECB/CBC detection oracle of challenge 11.
+
+use std::collections::HashSet;
+
+use crate::aes::{Aes128CBC, Aes128ECB};
+use crate::random::Rng;
+
+const BLOCK_SIZE: usize = 16;
+
+/// Number of identical blocks [`detect_mode`] feeds to the oracle.
+const PROBE_BLOCKS: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlockMode {
+    ECB,
+    CBC,
+}
+
+/// The result of [`detect_mode`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Detection {
+    pub mode: BlockMode,
+    /// How strongly the ciphertext supports `mode`, between 0.5 and 1.
+    pub confidence: f64,
+}
+
+/// Encrypts `input` under a random AES-128 key, in ECB or CBC mode with
+/// equal probability.
+///
+/// The input is surrounded by 5 to 10 random bytes on each side and padded
+/// with PKCS#7. CBC uses a random IV. Returns the ciphertext and the mode
+/// used, so that guesses can be checked.
+pub fn encryption_oracle(rng: &mut impl Rng, input: &[u8]) -> (Vec<u8>, BlockMode) {
+    let key = rng.random_key();
+
+    let prefix_len = rng.gen_range(5..11);
+    let prefix = rng.random_bytes(prefix_len);
+    let suffix_len = rng.gen_range(5..11);
+    let suffix = rng.random_bytes(suffix_len);
+    let plaintext = [&prefix[..], input, &suffix[..]].concat();
+
+    if rng.gen_bool() {
+        let cipher = Aes128ECB::new(&key);
+        (cipher.encrypt_padded(&plaintext), BlockMode::ECB)
+    } else {
+        let iv = rng.random_iv();
+        let cipher = Aes128CBC::new(&key);
+        (cipher.encrypt_padded(&plaintext, &iv), BlockMode::CBC)
+    }
+}
+
+/// Returns the number of blocks of `ciphertext` that are equal to an earlier
+/// block.
+///
+/// # Panics
+/// The function panics if `block_size` is zero.
+pub fn count_repeated_blocks(ciphertext: &[u8], block_size: usize) -> usize {
+    assert!(block_size > 0, "block size must not be zero");
+    let mut seen = HashSet::new();
+
+    ciphertext
+        .chunks_exact(block_size)
+        .filter(|block| !seen.insert(*block))
+        .count()
+}
+
+/// Guesses whether `oracle` encrypts in ECB or CBC mode.
+///
+/// The oracle is queried once with identical blocks, plus one more block to
+/// absorb a prefix of up to a block. ECB encrypts them to identical
+/// ciphertext blocks, while CBC repeats a block only by chance. The
+/// confidence is the fraction of the expected repeats that were seen for
+/// ECB, or that were missing for CBC.
+pub fn detect_mode(mut oracle: impl FnMut(&[u8]) -> Vec<u8>) -> Detection {
+    let input = [0u8; (PROBE_BLOCKS + 1) * BLOCK_SIZE];
+    let ciphertext = oracle(&input);
+
+    let expected = PROBE_BLOCKS - 1;
+    let repeated = count_repeated_blocks(&ciphertext, BLOCK_SIZE).min(expected);
+    let ratio = repeated as f64 / expected as f64;
+
+    if 2 * repeated >= expected {
+        Detection {
+            mode: BlockMode::ECB,
+            confidence: ratio,
+        }
+    } else {
+        Detection {
+            mode: BlockMode::CBC,
+            confidence: 1.0 - ratio,
+        }
+    }
+}
@@ -0,0 +1,234 @@
+//! Sources of random keys, IVs and nonces.
+//!
+//! [`SecureRandom`] reads from the operating system and is what the oracles
+//! use. [`SeededRng`] produces the same stream for the same seed, which makes
+//! tests of randomised oracles reproducible.
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+use std::fs::File;
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+use std::io::Read;
+use std::ops::Range;
+
+use crate::Error;
+
+pub trait Rng {
+    /// Fills `buf` with random bytes.
+    fn fill_bytes(&mut self, buf: &mut [u8]);
+
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0u8; 8];
+        self.fill_bytes(&mut bytes);
+
+        u64::from_le_bytes(bytes)
+    }
+
+    /// Returns a uniformly distributed value in `range`.
+    ///
+    /// # Panics
+    /// The function panics if `range` is empty.
+    fn gen_range(&mut self, range: Range<usize>) -> usize {
+        assert!(!range.is_empty(), "empty range");
+        let span = (range.end - range.start) as u64;
+
+        // Reject the values of the last incomplete multiple of `span` to
+        // avoid modulo bias.
+        let zone = u64::MAX - u64::MAX % span;
+        loop {
+            let x = self.next_u64();
+            if x < zone {
+                return range.start + (x % span) as usize;
+            }
+        }
+    }
+
+    fn gen_bool(&mut self) -> bool {
+        self.next_u64() & 1 == 1
+    }
+
+    fn random_bytes(&mut self, len: usize) -> Vec<u8> {
+        let mut buf = vec![0u8; len];
+        self.fill_bytes(&mut buf);
+
+        buf
+    }
+
+    /// Returns a random nonce of `N` bytes, for example 12 for GCM.
+    fn random_nonce<const N: usize>(&mut self) -> [u8; N] {
+        let mut nonce = [0u8; N];
+        self.fill_bytes(&mut nonce);
+
+        nonce
+    }
+
+    /// Returns a random AES-128 key.
+    fn random_key(&mut self) -> [u8; 16] {
+        self.random_nonce()
+    }
+
+    /// Returns a random IV for a cipher with 16 byte blocks.
+    fn random_iv(&mut self) -> [u8; 16] {
+        self.random_nonce()
+    }
+}
+
+/// Random bytes from the operating system.
+///
+/// On Linux and Android this is the `getrandom` syscall, which needs no file
+/// descriptor and blocks only until the kernel pool is first seeded. Other
+/// Unix systems have no common syscall for this, so `/dev/urandom` is read
+/// instead.
+pub struct SecureRandom {
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    source: File,
+}
+
+impl SecureRandom {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn new() -> Result<Self, Error> {
+        Ok(Self {})
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    pub fn new() -> Result<Self, Error> {
+        let source = File::open("/dev/urandom")?;
+
+        Ok(Self { source })
+    }
+
+    /// Fills `buf` with random bytes, or returns the error of the operating
+    /// system.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn try_fill_bytes(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        let mut pos = 0;
+
+        // Large requests can be cut short by a signal, so loop until done.
+        while pos < buf.len() {
+            let rest = &mut buf[pos..];
+            // SAFETY: `rest` is valid for writes of `rest.len()` bytes.
+            let n = unsafe { libc::getrandom(rest.as_mut_ptr().cast(), rest.len(), 0) };
+
+            if n < 0 {
+                let err = std::io::Error::last_os_error();
+                if err.kind() == std::io::ErrorKind::Interrupted {
+                    continue;
+                }
+
+                return Err(err.into());
+            }
+
+            pos += n as usize;
+        }
+
+        Ok(())
+    }
+
+    /// Fills `buf` with random bytes, or returns the error of the operating
+    /// system.
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    pub fn try_fill_bytes(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        self.source.read_exact(buf)?;
+
+        Ok(())
+    }
+}
+
+impl Rng for SecureRandom {
+    /// # Panics
+    /// The function panics if the operating system cannot provide random
+    /// bytes. Use [`SecureRandom::try_fill_bytes`] to handle that instead.
+    fn fill_bytes(&mut self, buf: &mut [u8]) {
+        self.try_fill_bytes(buf)
+            .expect("the operating system failed to provide random bytes");
+    }
+}
+
+/// A deterministic xoshiro256** generator.
+///
+/// This is not cryptographically secure: the output reveals the state.
+#[derive(Clone, Debug)]
+pub struct SeededRng {
+    state: [u64; 4],
+}
+
+impl SeededRng {
+    pub fn new(seed: u64) -> Self {
+        // Expand the seed with SplitMix64, which never yields the all zero
+        // state.
+        let mut x = seed;
+        let state = std::array::from_fn(|_| {
+            x = x.wrapping_add(0x9e3779b97f4a7c15);
+            let mut z = x;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+            z ^ (z >> 31)
+        });
+
+        Self { state }
+    }
+}
+
+impl Rng for SeededRng {
+    fn fill_bytes(&mut self, buf: &mut [u8]) {
+        for chunk in buf.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let s = &mut self.state;
+        let result = s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+
+        let t = s[1] << 17;
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+
+        result
+    }
+}
+
+/// Fills `buf` with bytes from [`SecureRandom`].
+pub fn try_fill_random(buf: &mut [u8]) -> Result<(), Error> {
+    SecureRandom::new()?.try_fill_bytes(buf)
+}
+
+/// Like [`try_fill_random`], but panics instead of returning an error.
+///
+/// # Panics
+/// The function panics if the operating system cannot provide random bytes.
+pub fn fill_random(buf: &mut [u8]) {
+    SecureRandom::new()
+        .and_then(|mut rng| rng.try_fill_bytes(buf))
+        .expect("the operating system failed to provide random bytes");
+}
+
+// The helpers below draw from the operating system through the `Rng`
+// methods of the same name. Oracles that take an `Rng` call those methods
+// directly, so a `SeededRng` makes them reproducible.
+
+pub fn random_bytes(len: usize) -> Vec<u8> {
+    os_rng().random_bytes(len)
+}
+
+pub fn random_key() -> [u8; 16] {
+    os_rng().random_key()
+}
+
+pub fn random_iv() -> [u8; 16] {
+    os_rng().random_iv()
+}
+
+pub fn random_nonce<const N: usize>() -> [u8; N] {
+    os_rng().random_nonce()
+}
+
+/// # Panics
+/// The function panics if the operating system RNG cannot be opened.
+fn os_rng() -> SecureRandom {
+    SecureRandom::new().expect("failed to open the operating system RNG")
+}
@@ -24,7 +24,9 @@ fn test_hamming_distance() {
 }
 
 mod aes {
-    use crate::{aes, base64::from_base64, Base64};
+    use crate::modes::BlockCipher;
+    use crate::{aes, base64::from_base64, from_hex, xor, Base64, Error};
+    use anyhow::Result;
     const KEY: &[u8; 16] = b"\x00\x01\x02\x03\x04\x05\x06\x07\x08\x09\x0a\x0b\x0c\x0d\x0e\x0f";
     const PLAIN: &[u8; 16] = b"\x00\x11\x22\x33\x44\x55\x66\x77\x88\x99\xaa\xbb\xcc\xdd\xee\xff";
     const CIPHER: &[u8; 16] = b"\x69\xc4\xe0\xd8\x6a\x7b\x04\x30\xd8\xcd\xb7\x80\x70\xb4\xc5\x5a";
@@ -60,7 +62,7 @@ mod aes {
             [0x4d, 0x7e, 0xbd, 0xf8],
         ];
 
-        for (input, expected) in input_vectors.into_iter().zip(output_vectors.into_iter()) {
+        for (input, expected) in input_vectors.into_iter().zip(output_vectors) {
             let word = u32::from_le_bytes(input);
             let actual = aes::mix_word(word);
 
@@ -88,7 +90,7 @@ mod aes {
             [0x4d, 0x7e, 0xbd, 0xf8],
         ];
 
-        for (input, expected) in input_vectors.into_iter().zip(output_vectors.into_iter()) {
+        for (input, expected) in input_vectors.into_iter().zip(output_vectors) {
             let word = u32::from_le_bytes(input);
             let actual = aes::inv_mix_word(word);
 
@@ -128,34 +130,85 @@ mod aes {
         assert_eq!(input, output)
     }
 
+    // FIPS-197 Appendix A.1
+    const ROUND_KEY_128: [u8; 16] = [
+        0x2b, 0x7e, 0x15, 0x16, 0x28, 0xae, 0xd2, 0xa6, 0xab, 0xf7, 0x15, 0x88, 0x09, 0xcf, 0x4f,
+        0x3c,
+    ];
+    const ROUND_KEYS_128: [u32; 44] = [
+        0x2b7e1516, 0x28aed2a6, 0xabf71588, 0x09cf4f3c, 0xa0fafe17, 0x88542cb1, 0x23a33939,
+        0x2a6c7605, 0xf2c295f2, 0x7a96b943, 0x5935807a, 0x7359f67f, 0x3d80477d, 0x4716fe3e,
+        0x1e237e44, 0x6d7a883b, 0xef44a541, 0xa8525b7f, 0xb671253b, 0xdb0bad00, 0xd4d1c6f8,
+        0x7c839d87, 0xcaf2b8bc, 0x11f915bc, 0x6d88a37a, 0x110b3efd, 0xdbf98641, 0xca0093fd,
+        0x4e54f70e, 0x5f5fc9f3, 0x84a64fb2, 0x4ea6dc4f, 0xead27321, 0xb58dbad2, 0x312bf560,
+        0x7f8d292f, 0xac7766f3, 0x19fadc21, 0x28d12941, 0x575c006e, 0xd014f9a8, 0xc9ee2589,
+        0xe13f0cc8, 0xb6630ca6,
+    ];
+
     #[test]
     fn test_round_keys() {
-        let key: [u8; 16] = [
-            0x2b, 0x7e, 0x15, 0x16, 0x28, 0xae, 0xd2, 0xa6, 0xab, 0xf7, 0x15, 0x88, 0x09, 0xcf,
-            0x4f, 0x3c,
-        ];
-
-        let expected: [u32; 44] = [
-            0x2b7e1516, 0x28aed2a6, 0xabf71588, 0x09cf4f3c, 0xa0fafe17, 0x88542cb1, 0x23a33939,
-            0x2a6c7605, 0xf2c295f2, 0x7a96b943, 0x5935807a, 0x7359f67f, 0x3d80477d, 0x4716fe3e,
-            0x1e237e44, 0x6d7a883b, 0xef44a541, 0xa8525b7f, 0xb671253b, 0xdb0bad00, 0xd4d1c6f8,
-            0x7c839d87, 0xcaf2b8bc, 0x11f915bc, 0x6d88a37a, 0x110b3efd, 0xdbf98641, 0xca0093fd,
-            0x4e54f70e, 0x5f5fc9f3, 0x84a64fb2, 0x4ea6dc4f, 0xead27321, 0xb58dbad2, 0x312bf560,
-            0x7f8d292f, 0xac7766f3, 0x19fadc21, 0x28d12941, 0x575c006e, 0xd014f9a8, 0xc9ee2589,
-            0xe13f0cc8, 0xb6630ca6,
-        ]
-        .map(|w: u32| w.to_be());
+        let expected = ROUND_KEYS_128.map(|w: u32| w.to_be());
 
-        let actual = aes::create_round_keys(&key);
+        let actual = aes::create_round_keys(&ROUND_KEY_128);
 
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn test_invert_key_schedule() -> Result<()> {
+        let expected = ROUND_KEYS_128.map(|w: u32| w.to_be());
+
+        for round in 0..=10 {
+            let round_key: Vec<u8> = ROUND_KEYS_128[4 * round..4 * round + 4]
+                .iter()
+                .flat_map(|w| w.to_be_bytes())
+                .collect();
+
+            let actual = aes::invert_key_schedule::<aes::Key128>(&round_key, round)?;
+            assert_eq!(actual, expected, "round {round}");
+            assert_eq!(
+                aes::recover_key::<aes::Key128>(&round_key, round)?,
+                ROUND_KEY_128
+            );
+        }
+
+        assert!(aes::invert_key_schedule::<aes::Key128>(&ROUND_KEY_128, 11).is_err());
+        assert!(aes::invert_key_schedule::<aes::Key128>(&ROUND_KEY_128[..12], 0).is_err());
+
+        Ok(())
+    }
+
+    fn check_invert_key_schedule<K: aes::KeySize>(max_round: usize) -> Result<()> {
+        let key: Vec<u8> = (0..4 * K::NK as u8).collect();
+        let expected = aes::create_round_keys(&key);
+
+        for round in 0..=max_round {
+            let window: Vec<u8> = expected[4 * round..4 * round + K::NK]
+                .iter()
+                .flat_map(|w| w.to_le_bytes())
+                .collect();
+
+            assert_eq!(aes::invert_key_schedule::<K>(&window, round)?, expected);
+            assert_eq!(aes::recover_key::<K>(&window, round)?, key);
+        }
+
+        let window = vec![0u8; 4 * K::NK];
+        assert!(aes::invert_key_schedule::<K>(&window, max_round + 1).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invert_key_schedule_192_256() -> Result<()> {
+        check_invert_key_schedule::<aes::Key192>(11)?;
+        check_invert_key_schedule::<aes::Key256>(13)
+    }
+
     #[test]
     fn test_encrypt_block() {
         let cipher = aes::Aes128ECB::new(KEY);
         let mut output = vec![0u8; CIPHER.len()];
-        cipher.encrypt(PLAIN, &mut output);
+        cipher.encrypt(PLAIN, &mut output).unwrap();
 
         assert_eq!(&output, CIPHER);
     }
@@ -164,12 +217,348 @@ mod aes {
     fn test_decrypt_block() {
         let cipher = aes::Aes128ECB::new(KEY);
         let mut output = vec![0u8; CIPHER.len()];
-        cipher.decrypt(CIPHER, &mut output);
+        cipher.decrypt(CIPHER, &mut output).unwrap();
 
         assert_eq!(&output, PLAIN);
     }
 
-    use anyhow::Result;
+    #[test]
+    fn test_round_keys_192_256() {
+        let key: [u8; 24] = [
+            0x8e, 0x73, 0xb0, 0xf7, 0xda, 0x0e, 0x64, 0x52, 0xc8, 0x10, 0xf3, 0x2b, 0x80, 0x90,
+            0x79, 0xe5, 0x62, 0xf8, 0xea, 0xd2, 0x52, 0x2c, 0x6b, 0x7b,
+        ];
+        let actual = aes::create_round_keys(&key);
+        assert_eq!(actual.len(), 52);
+        assert_eq!(actual[6], 0xfe0c91f7u32.to_be());
+        assert_eq!(actual[51], 0x01002202u32.to_be());
+
+        let key: [u8; 32] = [
+            0x60, 0x3d, 0xeb, 0x10, 0x15, 0xca, 0x71, 0xbe, 0x2b, 0x73, 0xae, 0xf0, 0x85, 0x7d,
+            0x77, 0x81, 0x1f, 0x35, 0x2c, 0x07, 0x3b, 0x61, 0x08, 0xd7, 0x2d, 0x98, 0x10, 0xa3,
+            0x09, 0x14, 0xdf, 0xf4,
+        ];
+        let actual = aes::create_round_keys(&key);
+        assert_eq!(actual.len(), 60);
+        assert_eq!(actual[8], 0x9ba35411u32.to_be());
+        assert_eq!(actual[12], 0xa8b09c1au32.to_be());
+        assert_eq!(actual[59], 0x706c631eu32.to_be());
+    }
+
+    // FIPS-197 Appendix C
+    #[test]
+    fn test_aes_192_256_block() {
+        let key: Vec<u8> = (0..32).collect();
+
+        let vectors: [(usize, &[u8; 16]); 2] = [
+            (
+                24,
+                b"\xdd\xa9\x7c\xa4\x86\x4c\xdf\xe0\x6e\xaf\x70\xa0\xec\x0d\x71\x91",
+            ),
+            (
+                32,
+                b"\x8e\xa2\xb7\xca\x51\x67\x45\xbf\xea\xfc\x49\x90\x4b\x49\x60\x89",
+            ),
+        ];
+
+        for (key_len, expected) in vectors {
+            let key = &key[..key_len];
+            let mut ciphertext = [0u8; 16];
+            let mut plaintext = [0u8; 16];
+
+            if key_len == 24 {
+                let cipher = aes::Aes192ECB::new(key);
+                cipher.encrypt(PLAIN, &mut ciphertext).unwrap();
+                cipher.decrypt(&ciphertext, &mut plaintext).unwrap();
+            } else {
+                let cipher = aes::Aes256ECB::new(key);
+                cipher.encrypt(PLAIN, &mut ciphertext).unwrap();
+                cipher.decrypt(&ciphertext, &mut plaintext).unwrap();
+            }
+
+            assert_eq!(&ciphertext, expected);
+            assert_eq!(&plaintext, PLAIN);
+        }
+    }
+
+    fn assert_backends_agree<K: aes::KeySize>(backend: aes::Backend) {
+        let key: Vec<u8> = (0..4 * K::NK as u8).map(|i| i.wrapping_mul(29)).collect();
+        let reference = aes::AesCore::<K>::new(&key).with_backend(aes::Backend::Reference);
+        let other = aes::AesCore::<K>::try_new_with_backend(&key, backend).unwrap();
+
+        let mut block = *PLAIN;
+        for _ in 0..64 {
+            let mut expected = block;
+            let mut actual = block;
+            reference.encrypt_block(&mut expected);
+            other.encrypt_block(&mut actual);
+            assert_eq!(actual, expected, "{backend:?} encrypt");
+
+            other.decrypt_block(&mut actual);
+            assert_eq!(actual, block, "{backend:?} decrypt");
+
+            // Feed the ciphertext back in to cover many different states.
+            block = expected;
+        }
+    }
+
+    #[test]
+    fn test_ttable_matches_reference() {
+        assert_backends_agree::<aes::Key128>(aes::Backend::TTable);
+        assert_backends_agree::<aes::Key192>(aes::Backend::TTable);
+        assert_backends_agree::<aes::Key256>(aes::Backend::TTable);
+
+        let cipher = aes::Aes128ECB::new(KEY).with_backend(aes::Backend::TTable);
+        let mut output = vec![0u8; CIPHER.len()];
+        cipher.encrypt(PLAIN, &mut output).unwrap();
+        assert_eq!(&output, CIPHER);
+    }
+
+    #[test]
+    fn test_bitsliced_matches_reference() {
+        assert_backends_agree::<aes::Key128>(aes::Backend::Bitsliced);
+        assert_backends_agree::<aes::Key192>(aes::Backend::Bitsliced);
+        assert_backends_agree::<aes::Key256>(aes::Backend::Bitsliced);
+
+        let cipher = aes::Aes128ECB::new(KEY).with_backend(aes::Backend::Bitsliced);
+        let mut output = vec![0u8; CIPHER.len()];
+        cipher.encrypt(PLAIN, &mut output).unwrap();
+        assert_eq!(&output, CIPHER);
+    }
+
+    #[test]
+    fn test_aesni_matches_reference() {
+        if !aes::Backend::AesNi.is_available() {
+            return;
+        }
+
+        assert_backends_agree::<aes::Key128>(aes::Backend::AesNi);
+        assert_backends_agree::<aes::Key192>(aes::Backend::AesNi);
+        assert_backends_agree::<aes::Key256>(aes::Backend::AesNi);
+        assert_eq!(aes::Backend::detect(), aes::Backend::AesNi);
+    }
+
+    #[test]
+    fn test_aesni_round_keys() {
+        let key: Vec<u8> = (0..32u8).map(|i| i.wrapping_mul(37) ^ 0x5a).collect();
+
+        for len in [16, 24, 32] {
+            let Some(actual) = aes::create_round_keys_aesni(&key[..len]) else {
+                return;
+            };
+
+            assert_eq!(
+                actual,
+                aes::create_round_keys(&key[..len]),
+                "{len} byte key"
+            );
+        }
+    }
+
+    #[test]
+    fn test_reduced_rounds() {
+        let full = aes::Aes128ECB::new(KEY);
+        let same = aes::Aes128ECB::new(KEY).with_rounds(10);
+        let mut expected = [0u8; 16];
+        let mut actual = [0u8; 16];
+        full.encrypt(PLAIN, &mut expected).unwrap();
+        same.encrypt(PLAIN, &mut actual).unwrap();
+        assert_eq!(actual, expected);
+
+        for backend in [
+            aes::Backend::Reference,
+            aes::Backend::TTable,
+            aes::Backend::Bitsliced,
+            aes::Backend::AesNi,
+        ] {
+            if !backend.is_available() {
+                continue;
+            }
+
+            let reference = aes::Aes128ECB::new(KEY)
+                .with_backend(aes::Backend::Reference)
+                .with_rounds(4);
+            let cipher = aes::Aes128ECB::new(KEY)
+                .with_backend(backend)
+                .with_rounds(4);
+            assert_eq!(cipher.cipher().rounds(), 4);
+
+            reference.encrypt(PLAIN, &mut expected).unwrap();
+            cipher.encrypt(PLAIN, &mut actual).unwrap();
+            assert_eq!(actual, expected, "{backend:?}");
+            assert_ne!(&actual, CIPHER);
+
+            cipher.decrypt(&expected, &mut actual).unwrap();
+            assert_eq!(&actual, PLAIN, "{backend:?}");
+        }
+    }
+
+    #[test]
+    fn test_square_attack() {
+        let key = b"YELLOW SUBMARINE";
+        let cipher = aes::Aes128ECB::new(key).with_rounds(aes::square::ROUNDS);
+
+        let recovered = aes::square::attack(|plaintext| {
+            let mut ciphertext = [0u8; 16];
+            cipher.encrypt(plaintext, &mut ciphertext).unwrap();
+            ciphertext
+        });
+        assert_eq!(recovered.as_deref(), Some(&key[..]));
+
+        // One more round breaks the integral property.
+        let cipher = aes::Aes128ECB::new(key).with_rounds(5);
+        let recovered = aes::square::attack(|plaintext| {
+            let mut ciphertext = [0u8; 16];
+            cipher.encrypt(plaintext, &mut ciphertext).unwrap();
+            ciphertext
+        });
+        assert_eq!(recovered, None);
+    }
+
+    // NIST SP 800-38A F.5.1
+    #[test]
+    fn test_ctr_encrypt() -> Result<()> {
+        let key = from_hex("2b7e151628aed2a6abf7158809cf4f3c")?;
+        let iv: [u8; 16] = from_hex("f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff")?
+            .try_into()
+            .unwrap();
+        let plaintext = from_hex(concat!(
+            "6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51",
+            "30c81c46a35ce411e5fbc1191a0a52eff69f2445df4f9b17ad2b417be66c3710"
+        ))?;
+        let ciphertext = from_hex(concat!(
+            "874d6191b620e3261bef6864990db6ce9806f66b7970fdff8617187bb9fffdff",
+            "5ae4df3edbd5d35e5b4f09020db03eab1e031dda2fbe03d1792170a0f3009cee"
+        ))?;
+
+        let cipher = aes::Aes128CTR::new(&key);
+        let layout = aes::CounterLayout::BigEndian128;
+
+        let mut output = vec![0u8; plaintext.len()];
+        cipher.encrypt(&plaintext, &mut output, &iv, layout)?;
+        assert_eq!(output, ciphertext);
+
+        // A message that is not a multiple of the block size is not padded.
+        let mut output = vec![0u8; 37];
+        cipher.decrypt(&ciphertext[..37], &mut output, &iv, layout)?;
+        assert_eq!(output, &plaintext[..37]);
+
+        // Seeking into the middle of a block.
+        let mut output = vec![0u8; 20];
+        cipher.encrypt_at(&plaintext[21..41], &mut output, &iv, layout, 21)?;
+        assert_eq!(output, &ciphertext[21..41]);
+
+        Ok(())
+    }
+
+    // NIST SP 800-38A F.3.13, F.3.7, F.4.1
+    #[test]
+    fn test_cfb_ofb() -> Result<()> {
+        let key = from_hex("2b7e151628aed2a6abf7158809cf4f3c")?;
+        let iv: [u8; 16] = from_hex("000102030405060708090a0b0c0d0e0f")?
+            .try_into()
+            .unwrap();
+        let plaintext = from_hex(concat!(
+            "6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51",
+            "30c81c46a35ce411e5fbc1191a0a52eff69f2445df4f9b17ad2b417be66c3710"
+        ))?;
+        let mut output = vec![0u8; plaintext.len()];
+
+        let cfb = aes::Aes128CFB::new(&key);
+        let expected = from_hex(concat!(
+            "3b3fd92eb72dad20333449f8e83cfb4ac8a64537a0b3a93fcde3cdad9f1ce58b",
+            "26751f67a3cbb140b1808cf187a4f4dfc04b05357c5d1c0eeac4c66f9ff7f2e6"
+        ))?;
+        cfb.encrypt(&plaintext, &mut output, &iv)?;
+        assert_eq!(output, expected);
+        cfb.decrypt(&expected, &mut output, &iv)?;
+        assert_eq!(output, plaintext);
+
+        let ofb = aes::Aes128OFB::new(&key);
+        let expected = from_hex(concat!(
+            "3b3fd92eb72dad20333449f8e83cfb4a7789508d16918f03f53c52dac54ed825",
+            "9740051e9c5fecf64344f7a82260edcc304c6528f659c77866a510d9c1d6ae5e"
+        ))?;
+        ofb.encrypt(&plaintext, &mut output, &iv)?;
+        assert_eq!(output, expected);
+        ofb.decrypt(&expected, &mut output, &iv)?;
+        assert_eq!(output, plaintext);
+
+        let cfb8 = aes::Aes128CFB8::new(&key);
+        let expected = from_hex("3b79424c9c0dd436bace9e0ed4586a4f32b9")?;
+        let mut output = vec![0u8; expected.len()];
+        cfb8.encrypt(&plaintext[..18], &mut output, &iv)?;
+        assert_eq!(output, expected);
+        cfb8.decrypt(&expected, &mut output, &iv)?;
+        assert_eq!(output, &plaintext[..18]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pcbc_error_propagation() {
+        let plaintext = b"YELLOW SUBMARINEYELLOW SUBMARINEYELLOW SUBMARINE";
+        let key = b"YELLOW SUBMARINE";
+        let iv = [0u8; 16];
+
+        let cipher = aes::Aes128PCBC::new(key);
+        let mut ciphertext = vec![0u8; plaintext.len()];
+        cipher.encrypt(plaintext, &mut ciphertext, &iv).unwrap();
+
+        let mut output = vec![0u8; plaintext.len()];
+        cipher.decrypt(&ciphertext, &mut output, &iv).unwrap();
+        assert_eq!(output, plaintext);
+
+        // Unlike CBC, a flipped bit garbles every block after it.
+        ciphertext[0] ^= 1;
+        cipher.decrypt(&ciphertext, &mut output, &iv).unwrap();
+        for (actual, expected) in output.chunks(16).zip(plaintext.chunks(16)) {
+            assert_ne!(actual, expected);
+        }
+    }
+
+    #[test]
+    fn test_unpad() {
+        let padded = aes::pad(b"YELLOW SUBMARINE", 16);
+        assert_eq!(padded.len(), 32);
+        assert_eq!(aes::unpad(&padded, 16).unwrap(), b"YELLOW SUBMARINE");
+
+        let mut padded = aes::pad(b"YELLOW", 8);
+        aes::unpad_in_place(&mut padded, 8).unwrap();
+        assert_eq!(padded, b"YELLOW");
+    }
+
+    #[test]
+    fn test_unpad_invalid() {
+        use aes::PaddingError;
+
+        assert_eq!(aes::validate_padding(b"", 16), Err(PaddingError::Empty));
+        assert_eq!(
+            aes::validate_padding(b"YELLOW SUBMARINE", 16),
+            Err(PaddingError::InvalidPadByte(b'E'))
+        );
+        assert_eq!(
+            aes::validate_padding(b"YELLOW SUBMARIN\x00", 16),
+            Err(PaddingError::InvalidPadByte(0))
+        );
+        assert_eq!(
+            aes::validate_padding(b"\x03\x03", 2),
+            Err(PaddingError::InvalidPadByte(3))
+        );
+        assert_eq!(
+            aes::validate_padding(b"YELLOW\x02\x02", 16),
+            Err(PaddingError::NotBlockAligned)
+        );
+        assert_eq!(
+            aes::validate_padding(b"ICE ICE BABY\x01\x02\x03\x04", 16),
+            Err(PaddingError::InconsistentPadding)
+        );
+
+        let mut block = b"ICE ICE BABY\x05\x05\x05\x05".to_vec();
+        assert!(aes::unpad_in_place(&mut block, 16).is_err());
+        assert_eq!(block, b"ICE ICE BABY\x05\x05\x05\x05");
+    }
+
     #[test]
     fn test_cbc_encrypt() -> Result<()> {
         let plaintext = b"YELLOW SUBMARINEYELLOW SUBMARINEYELLOW SUBMARINEYELLOW SUBMARINE";
@@ -177,7 +566,7 @@ mod aes {
 
         let cipher = aes::Aes128CBC::new(key);
         let mut output = vec![0u8; plaintext.len()];
-        cipher.encrypt(plaintext, &mut output, *key);
+        cipher.encrypt(plaintext, &mut output, key)?;
 
         let expected_cipher = "dtHLS6+iRuLjrwNdbBPDctTfTe4kqljmNVSzVoBDL9oj5/DXE4QVZrjXTL3oHbiu1AOyUyCu75ZxZ70pWO7WPA==";
         assert_eq!(expected_cipher, output.to_base64());
@@ -194,11 +583,1460 @@ mod aes {
         let cipher = aes::Aes128CBC::new(key);
         let input = from_base64(ciphertext)?;
         let mut output = vec![0u8; input.len()];
-        cipher.decrypt(&input, &mut output, key);
+        cipher.decrypt(&input, &mut output, key)?;
 
         dbg!(&output);
         assert_eq!(plaintext, str::from_utf8(&output)?);
 
         Ok(())
     }
+
+    #[test]
+    fn test_invalid_lengths_are_errors() {
+        assert!(matches!(
+            aes::Aes128ECB::try_new(b"YELLOW"),
+            Err(Error::InvalidKeyLength {
+                len: 6,
+                expected: 16
+            })
+        ));
+
+        let cipher = aes::Aes128CBC::new(b"YELLOW SUBMARINE");
+        let mut output = [0u8; 32];
+        assert!(matches!(
+            cipher.encrypt(&[0u8; 16], &mut output, &[0u8; 16]),
+            Err(Error::LengthMismatch {
+                input: 16,
+                output: 32
+            })
+        ));
+        assert!(matches!(
+            cipher.decrypt(&[0u8; 20], &mut output[..20], &[0u8; 16]),
+            Err(Error::NotBlockAligned {
+                len: 20,
+                block_size: 16
+            })
+        ));
+
+        assert!(xor::try_xor(b"ICE", b"ICE ICE").is_err());
+    }
+
+    #[test]
+    fn test_padded_roundtrip() -> Result<()> {
+        let key = b"YELLOW SUBMARINE";
+        let iv = [0u8; 16];
+
+        let ecb = aes::Aes128ECB::new(key);
+        let cbc = aes::Aes128CBC::new(key);
+
+        for len in [0, 1, 15, 16, 17, 32] {
+            let plaintext = vec![b'A'; len];
+
+            let ciphertext = ecb.encrypt_padded(&plaintext);
+            assert_eq!(ciphertext.len(), (len / 16 + 1) * 16);
+            assert_eq!(ecb.decrypt_padded(&ciphertext)?, plaintext);
+
+            let ciphertext = cbc.encrypt_padded(&plaintext, &iv);
+            assert_eq!(cbc.decrypt_padded(&ciphertext, &iv)?, plaintext);
+        }
+
+        // A wrong IV corrupts the padding of a single block message.
+        let ciphertext = cbc.encrypt_padded(b"YELLOW", &iv);
+        assert!(cbc.decrypt_padded(&ciphertext, &[1u8; 16]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_in_place_matches_out_of_place() -> Result<()> {
+        let key = b"YELLOW SUBMARINE";
+        let iv = *b"ICE ICE BABY ICE";
+        let plaintext: Vec<u8> = (0..64).collect();
+        let mut expected = vec![0u8; plaintext.len()];
+
+        // The block aligned modes return a Result from their in place methods,
+        // the stream-like modes cannot fail.
+        macro_rules! check {
+            (fallible: $cipher:expr, $($args:expr),*) => {{
+                let cipher = $cipher;
+                cipher.encrypt(&plaintext, &mut expected, $($args),*)?;
+
+                let mut buf = plaintext.clone();
+                cipher.encrypt_in_place(&mut buf, $($args),*)?;
+                assert_eq!(buf, expected);
+
+                cipher.decrypt_in_place(&mut buf, $($args),*)?;
+                assert_eq!(buf, plaintext);
+            }};
+            ($cipher:expr, $($args:expr),*) => {{
+                let cipher = $cipher;
+                cipher.encrypt(&plaintext, &mut expected, $($args),*)?;
+
+                let mut buf = plaintext.clone();
+                cipher.encrypt_in_place(&mut buf, $($args),*);
+                assert_eq!(buf, expected);
+
+                cipher.decrypt_in_place(&mut buf, $($args),*);
+                assert_eq!(buf, plaintext);
+            }};
+        }
+
+        check!(fallible: aes::Aes128CBC::new(key), &iv);
+        check!(fallible: aes::Aes128PCBC::new(key), &iv);
+        check!(aes::Aes128CFB::new(key), &iv);
+        check!(aes::Aes128CFB8::new(key), &iv);
+        check!(aes::Aes128OFB::new(key), &iv);
+        check!(
+            aes::Aes128CTR::new(key),
+            &iv,
+            aes::CounterLayout::BigEndian128
+        );
+
+        let ecb = aes::Aes128ECB::new(key);
+        ecb.encrypt(&plaintext, &mut expected)?;
+        let mut buf = plaintext.clone();
+        ecb.encrypt_in_place(&mut buf)?;
+        assert_eq!(buf, expected);
+
+        let gcm = aes::Aes128GCM::new(key);
+        let tag = gcm.seal(&iv[..12], b"aad", &plaintext, &mut expected)?;
+        let mut buf = plaintext.clone();
+        assert_eq!(gcm.seal_in_place(&iv[..12], b"aad", &mut buf)?, tag);
+        assert_eq!(buf, expected);
+        gcm.open_in_place(&iv[..12], b"aad", &mut buf, &tag)?;
+        assert_eq!(buf, plaintext);
+
+        let cbc = aes::Aes128CBC::new(key);
+        let mut buf = b"YELLOW SUBMARINE!".to_vec();
+        cbc.encrypt_padded_in_place(&mut buf, &iv);
+        assert_eq!(buf.len(), 32);
+        cbc.decrypt_padded_in_place(&mut buf, &iv)?;
+        assert_eq!(buf, b"YELLOW SUBMARINE!");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_multi_block_matches_single_block() -> Result<()> {
+        let key = b"YELLOW SUBMARINE";
+        let iv = *b"ICE ICE BABY ICE";
+
+        for backend in [
+            aes::Backend::Reference,
+            aes::Backend::TTable,
+            aes::Backend::AesNi,
+        ] {
+            if !backend.is_available() {
+                continue;
+            }
+
+            let ecb = aes::Aes128ECB::new(key).with_backend(backend);
+            let ctr = aes::Aes128CTR::new(key).with_backend(backend);
+
+            // Cover a partial batch on either side of a full one.
+            for blocks in [0, 1, 7, 8, 9, 17] {
+                let plaintext: Vec<u8> = (0..16 * blocks).map(|i| i as u8).collect();
+
+                let mut expected = plaintext.clone();
+                for chunk in expected.chunks_exact_mut(16) {
+                    let mut block = chunk.try_into()?;
+                    ecb.cipher().encrypt_block(&mut block);
+                    chunk.copy_from_slice(&block);
+                }
+
+                let mut buf = plaintext.clone();
+                ecb.encrypt_in_place(&mut buf)?;
+                assert_eq!(buf, expected, "{backend:?} ECB, {blocks} blocks");
+                ecb.decrypt_in_place(&mut buf)?;
+                assert_eq!(buf, plaintext);
+
+                // CTR with every unaligned start in the first block.
+                for offset in 0..16u64 {
+                    let mut expected = plaintext.clone();
+                    for (i, b) in expected.iter_mut().enumerate() {
+                        let pos = offset + i as u64;
+                        let mut keystream =
+                            aes::CounterLayout::BigEndian128.nth_block(&iv, pos / 16);
+                        ctr.cipher().encrypt_block(&mut keystream);
+                        *b ^= keystream[(pos % 16) as usize];
+                    }
+
+                    let mut buf = plaintext.clone();
+                    ctr.encrypt_at_in_place(
+                        &mut buf,
+                        &iv,
+                        aes::CounterLayout::BigEndian128,
+                        offset,
+                    );
+                    assert_eq!(
+                        buf, expected,
+                        "{backend:?} CTR, {blocks} blocks at {offset}"
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_threaded_matches_single_threaded() -> Result<()> {
+        let key = b"YELLOW SUBMARINE";
+        let iv = *b"ICE ICE BABY ICE";
+        let plaintext: Vec<u8> = (0..1 << 20).map(|i: u32| (i * 7) as u8).collect();
+
+        let ecb = aes::Aes128ECB::new(key);
+        let mut expected = plaintext.clone();
+        ecb.encrypt_in_place(&mut expected)?;
+        let mut buf = plaintext.clone();
+        ecb.encrypt_in_place_threaded(&mut buf)?;
+        assert_eq!(buf, expected);
+        ecb.decrypt_in_place_threaded(&mut buf)?;
+        assert_eq!(buf, plaintext);
+
+        // An odd length leaves a partial block in the last piece.
+        let plaintext = &plaintext[..plaintext.len() - 3];
+        let ctr = aes::Aes128CTR::new(key);
+        let mut expected = plaintext.to_vec();
+        ctr.encrypt_in_place(&mut expected, &iv, aes::CounterLayout::BigEndian32);
+        let mut buf = plaintext.to_vec();
+        ctr.encrypt_in_place_threaded(&mut buf, &iv, aes::CounterLayout::BigEndian32);
+        assert_eq!(buf, expected);
+        ctr.decrypt_in_place_threaded(&mut buf, &iv, aes::CounterLayout::BigEndian32);
+        assert_eq!(buf, plaintext);
+
+        Ok(())
+    }
+}
+
+mod gcm {
+    use crate::{aes, from_hex};
+    use anyhow::Result;
+
+    // Test cases 1-6 from "The Galois/Counter Mode of Operation (GCM)" by McGrew and Viega.
+    const VECTORS: [(&str, &str, &str, &str, &str, &str); 6] = [
+        (
+            "00000000000000000000000000000000",
+            "000000000000000000000000",
+            "",
+            "",
+            "",
+            "58e2fccefa7e3061367f1d57a4e7455a",
+        ),
+        (
+            "00000000000000000000000000000000",
+            "000000000000000000000000",
+            "00000000000000000000000000000000",
+            "",
+            "0388dace60b6a392f328c2b971b2fe78",
+            "ab6e47d42cec13bdf53a67b21257bddf",
+        ),
+        (
+            "feffe9928665731c6d6a8f9467308308",
+            "cafebabefacedbaddecaf888",
+            "d9313225f88406e5a55909c5aff5269a86a7a9531534f7da2e4c303d8a318a721c3c0c95956809532fcf0e2449a6b525b16aedf5aa0de657ba637b391aafd255",
+            "",
+            "42831ec2217774244b7221b784d0d49ce3aa212f2c02a4e035c17e2329aca12e21d514b25466931c7d8f6a5aac84aa051ba30b396a0aac973d58e091473f5985",
+            "4d5c2af327cd64a62cf35abd2ba6fab4",
+        ),
+        (
+            "feffe9928665731c6d6a8f9467308308",
+            "cafebabefacedbaddecaf888",
+            "d9313225f88406e5a55909c5aff5269a86a7a9531534f7da2e4c303d8a318a721c3c0c95956809532fcf0e2449a6b525b16aedf5aa0de657ba637b39",
+            "feedfacedeadbeeffeedfacedeadbeefabaddad2",
+            "42831ec2217774244b7221b784d0d49ce3aa212f2c02a4e035c17e2329aca12e21d514b25466931c7d8f6a5aac84aa051ba30b396a0aac973d58e091",
+            "5bc94fbc3221a5db94fae95ae7121a47",
+        ),
+        (
+            "feffe9928665731c6d6a8f9467308308",
+            "cafebabefacedbad",
+            "d9313225f88406e5a55909c5aff5269a86a7a9531534f7da2e4c303d8a318a721c3c0c95956809532fcf0e2449a6b525b16aedf5aa0de657ba637b39",
+            "feedfacedeadbeeffeedfacedeadbeefabaddad2",
+            "61353b4c2806934a777ff51fa22a4755699b2a714fcdc6f83766e5f97b6c742373806900e49f24b22b097544d4896b424989b5e1ebac0f07c23f4598",
+            "3612d2e79e3b0785561be14aaca2fccb",
+        ),
+        (
+            "feffe9928665731c6d6a8f9467308308",
+            "9313225df88406e555909c5aff5269aa6a7a9538534f7da1e4c303d2a318a728c3c0c95156809539fcf0e2429a6b525416aedbf5a0de6a57a637b39b",
+            "d9313225f88406e5a55909c5aff5269a86a7a9531534f7da2e4c303d8a318a721c3c0c95956809532fcf0e2449a6b525b16aedf5aa0de657ba637b39",
+            "feedfacedeadbeeffeedfacedeadbeefabaddad2",
+            "8ce24998625615b603a033aca13fb894be9112a5c3a211a8ba262a3cca7e2ca701e4a9a4fba43c90ccdcb281d48c7c6fd62875d2aca417034c34aee5",
+            "619cc5aefffe0bfa462af43c1699d050",
+        ),
+    ];
+
+    #[test]
+    fn test_seal_open() -> Result<()> {
+        for (key, nonce, plaintext, aad, ciphertext, tag) in VECTORS {
+            let cipher = aes::Aes128GCM::new(&from_hex(key)?);
+            let (nonce, aad) = (from_hex(nonce)?, from_hex(aad)?);
+            let (plaintext, ciphertext) = (from_hex(plaintext)?, from_hex(ciphertext)?);
+
+            let mut output = vec![0u8; plaintext.len()];
+            let actual_tag = cipher.seal(&nonce, &aad, &plaintext, &mut output)?;
+            assert_eq!(output, ciphertext);
+            assert_eq!(actual_tag.to_vec(), from_hex(tag)?);
+
+            cipher.open(&nonce, &aad, &ciphertext, &actual_tag, &mut output)?;
+            assert_eq!(output, plaintext);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_open_rejects_forgery() -> Result<()> {
+        let (key, nonce, _, aad, ciphertext, tag) = VECTORS[3];
+        let cipher = aes::Aes128GCM::new(&from_hex(key)?);
+        let (nonce, aad, tag) = (from_hex(nonce)?, from_hex(aad)?, from_hex(tag)?);
+
+        let mut ciphertext = from_hex(ciphertext)?;
+        ciphertext[0] ^= 1;
+
+        let mut output = vec![0u8; ciphertext.len()];
+        let res = cipher.open(&nonce, &aad, &ciphertext, &tag, &mut output);
+        assert!(matches!(res, Err(crate::Error::AuthenticationError(_))));
+        assert!(output.iter().all(|&b| b == 0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_truncated_tag() -> Result<()> {
+        let (key, nonce, _, aad, ciphertext, tag) = VECTORS[3];
+        let cipher = aes::Aes128GCM::new(&from_hex(key)?);
+        let (nonce, aad, tag) = (from_hex(nonce)?, from_hex(aad)?, from_hex(tag)?);
+        let ciphertext = from_hex(ciphertext)?;
+        let mut output = vec![0u8; ciphertext.len()];
+
+        // A prefix of a valid tag only passes when the receiver asks for it.
+        for len in [1, 8, 15] {
+            let res = cipher.open(&nonce, &aad, &ciphertext, &tag[..len], &mut output);
+            assert!(matches!(res, Err(crate::Error::AuthenticationError(_))));
+        }
+
+        cipher.open_truncated(&nonce, &aad, &ciphertext, &tag[..8], 8, &mut output)?;
+        cipher.open_truncated(&nonce, &aad, &ciphertext, &tag[..12], 12, &mut output)?;
+
+        let res = cipher.open_truncated(&nonce, &aad, &ciphertext, &tag[..12], 8, &mut output);
+        assert!(matches!(res, Err(crate::Error::AuthenticationError(_))));
+
+        for len in [0, 1, 6, 10, 17] {
+            let res = cipher.open_truncated(&nonce, &aad, &ciphertext, &tag, len, &mut output);
+            assert!(matches!(res, Err(crate::Error::InvalidTagLength { len: l }) if l == len));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_empty_nonce() {
+        let cipher = aes::Aes128GCM::new(&[0u8; 16]);
+        let mut buf = [0u8; 16];
+
+        let res = cipher.seal_in_place(&[], &[], &mut buf);
+        assert!(matches!(
+            res,
+            Err(crate::Error::InvalidNonceLength { len: 0 })
+        ));
+
+        let res = cipher.open_in_place(&[], &[], &mut buf, &[0u8; 16]);
+        assert!(matches!(
+            res,
+            Err(crate::Error::InvalidNonceLength { len: 0 })
+        ));
+    }
+
+    #[test]
+    fn test_gf128_mul() {
+        // x^0 is the multiplicative identity.
+        let one = aes::Gf128(1 << 127);
+        let a = aes::Gf128(0x66e94bd4ef8a2c3b884cfa59ca342b2e);
+
+        assert_eq!(a * one, a);
+        assert_eq!(one * a, a);
+        assert_eq!(a * aes::Gf128(0), aes::Gf128(0));
+    }
+}
+
+mod ccm {
+    use crate::{aes, from_hex};
+    use anyhow::Result;
+
+    // Examples 1-3 from NIST SP 800-38C Appendix C and packet vector 1 from RFC 3610.
+    const VECTORS: [(&str, &str, &str, &str, &str); 4] = [
+        (
+            "404142434445464748494a4b4c4d4e4f",
+            "10111213141516",
+            "0001020304050607",
+            "20212223",
+            "7162015b4dac255d",
+        ),
+        (
+            "404142434445464748494a4b4c4d4e4f",
+            "1011121314151617",
+            "000102030405060708090a0b0c0d0e0f",
+            "202122232425262728292a2b2c2d2e2f",
+            "d2a1f0e051ea5f62081a7792073d593d1fc64fbfaccd",
+        ),
+        (
+            "404142434445464748494a4b4c4d4e4f",
+            "101112131415161718191a1b",
+            "000102030405060708090a0b0c0d0e0f10111213",
+            "202122232425262728292a2b2c2d2e2f3031323334353637",
+            "e3b201a9f5b71a7a9b1ceaeccd97e70b6176aad9a4428aa5484392fbc1b09951",
+        ),
+        (
+            "c0c1c2c3c4c5c6c7c8c9cacbcccdcecf",
+            "00000003020100a0a1a2a3a4a5",
+            "0001020304050607",
+            "08090a0b0c0d0e0f101112131415161718191a1b1c1d1e",
+            "588c979a61c663d2f066d0c2c0f989806d5f6b61dac38417e8d12cfdf926e0",
+        ),
+    ];
+
+    fn check(
+        key: &[u8],
+        nonce: &[u8],
+        aad: &[u8],
+        plaintext: &[u8],
+        expected: &[u8],
+    ) -> Result<()> {
+        let cipher = aes::Aes128CCM::new(key);
+        let (ciphertext, tag) = expected.split_at(plaintext.len());
+
+        let mut output = vec![0u8; plaintext.len()];
+        let mut actual_tag = vec![0u8; tag.len()];
+        cipher.seal(nonce, aad, plaintext, &mut output, &mut actual_tag)?;
+        assert_eq!(output, ciphertext);
+        assert_eq!(actual_tag, tag);
+
+        cipher.open(nonce, aad, ciphertext, tag, &mut output)?;
+        assert_eq!(output, plaintext);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_seal_open() -> Result<()> {
+        for (key, nonce, aad, plaintext, expected) in VECTORS {
+            check(
+                &from_hex(key)?,
+                &from_hex(nonce)?,
+                &from_hex(aad)?,
+                &from_hex(plaintext)?,
+                &from_hex(expected)?,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_long_aad() -> Result<()> {
+        // Example 4 from NIST SP 800-38C Appendix C, whose AAD needs the 0xfffe length encoding.
+        let aad: Vec<u8> = (0..=255u8).cycle().take(1 << 16).collect();
+        let expected = from_hex(
+            "69915dad1e84c6376a68c2967e4dab615ae0fd1faec44cc484828529463ccf72\
+             b4ac6bec93e8598e7f0dadbcea5b",
+        )?;
+
+        check(
+            &from_hex("404142434445464748494a4b4c4d4e4f")?,
+            &from_hex("101112131415161718191a1b1c")?,
+            &aad,
+            &from_hex("202122232425262728292a2b2c2d2e2f303132333435363738393a3b3c3d3e3f")?,
+            &expected,
+        )
+    }
+
+    #[test]
+    fn test_open_rejects_forgery() -> Result<()> {
+        let (key, nonce, aad, plaintext, expected) = VECTORS[1];
+        let cipher = aes::Aes128CCM::new(&from_hex(key)?);
+        let (nonce, aad) = (from_hex(nonce)?, from_hex(aad)?);
+        let mut ciphertext = from_hex(expected)?;
+        let tag = ciphertext.split_off(from_hex(plaintext)?.len());
+
+        ciphertext[0] ^= 1;
+
+        let mut output = vec![0u8; ciphertext.len()];
+        let res = cipher.open(&nonce, &aad, &ciphertext, &tag, &mut output);
+        assert!(matches!(res, Err(crate::Error::AuthenticationError(_))));
+        assert!(output.iter().all(|&b| b == 0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_params() {
+        let cipher = aes::Aes128CCM::new(&[0u8; 16]);
+        let mut buf = [0u8; 16];
+
+        let res = cipher.seal_in_place(&[0u8; 6], &[], &mut buf, &mut [0u8; 8]);
+        assert!(matches!(
+            res,
+            Err(crate::Error::InvalidNonceLength { len: 6 })
+        ));
+
+        let res = cipher.seal_in_place(&[0u8; 14], &[], &mut buf, &mut [0u8; 8]);
+        assert!(matches!(
+            res,
+            Err(crate::Error::InvalidNonceLength { len: 14 })
+        ));
+
+        for len in [0, 2, 5, 18] {
+            let res = cipher.seal_in_place(&[0u8; 12], &[], &mut buf, &mut vec![0u8; len]);
+            assert!(matches!(res, Err(crate::Error::InvalidTagLength { .. })));
+        }
+
+        // A 13 byte nonce leaves 2 bytes for the message length.
+        let mut long = vec![0u8; 1 << 16];
+        let res = cipher.seal_in_place(&[0u8; 13], &[], &mut long, &mut [0u8; 8]);
+        assert!(matches!(
+            res,
+            Err(crate::Error::TooLong { max: 0xffff, .. })
+        ));
+    }
+}
+
+mod mac {
+    use crate::aes::{self, AesCore, Key128};
+    use crate::from_hex;
+    use crate::mac::{CbcMac, Cmac};
+    use anyhow::Result;
+
+    const KEY: &str = "2b7e151628aed2a6abf7158809cf4f3c";
+    const MESSAGE: &str = "6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51\
+                           30c81c46a35ce411e5fbc1191a0a52eff69f2445df4f9b17ad2b417be66c3710";
+
+    // RFC 4493 section 4
+    #[test]
+    fn test_cmac() -> Result<()> {
+        let cmac: aes::Aes128Cmac = Cmac::new(AesCore::<Key128>::new(&from_hex(KEY)?));
+        let message = from_hex(MESSAGE)?;
+
+        let (k1, k2) = cmac.subkeys();
+        assert_eq!(&k1[..], from_hex("fbeed618357133667c85e08f7236a8de")?);
+        assert_eq!(&k2[..], from_hex("f7ddac306ae266ccf90bc11ee46d513b")?);
+
+        let vectors = [
+            (0, "bb1d6929e95937287fa37d129b756746"),
+            (16, "070a16b46b4d4144f79bdd9dd04a287c"),
+            (40, "dfa66747de9ae63030ca32611497c827"),
+            (64, "51f0bebf7e3b9d92fc49741779363cfe"),
+        ];
+
+        for (len, tag) in vectors {
+            let tag = from_hex(tag)?;
+            assert_eq!(&cmac.mac(&message[..len])[..], tag, "{len} bytes");
+            cmac.verify(&message[..len], &tag)?;
+
+            let mut forged = tag.clone();
+            forged[15] ^= 1;
+            assert!(cmac.verify(&message[..len], &forged).is_err());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cbc_mac() -> Result<()> {
+        let key = from_hex(KEY)?;
+        let message = from_hex(MESSAGE)?;
+        let iv = *b"ICE ICE BABY ICE";
+
+        let cbc = aes::Aes128CBC::new(&key);
+        let mut ciphertext = vec![0u8; message.len()];
+        cbc.encrypt(&message, &mut ciphertext, &iv)?;
+
+        let mac: aes::Aes128CbcMac = CbcMac::new(AesCore::<Key128>::new(&key));
+        let tag = mac.mac_with_iv(&message, &iv)?;
+        assert_eq!(&tag[..], &ciphertext[48..]);
+        mac.verify_with_iv(&message, &iv, &tag)?;
+        assert!(mac.verify(&message, &tag).is_err());
+        assert!(mac.verify_with_iv(&message, &iv, &tag[..15]).is_err());
+        assert!(mac.mac(&message[..40]).is_err());
+
+        // A message with a known tag extends to one with a tag of our choice.
+        let mut extended = message.clone();
+        let mut block: [u8; 16] = message[..16].try_into()?;
+        crate::xor::xor_in_place(&mut block, &mac.mac(&message)?);
+        extended.extend_from_slice(&block);
+        extended.extend_from_slice(&message[16..]);
+        mac.verify(&extended, &mac.mac(&message)?)?;
+
+        Ok(())
+    }
+}
+
+mod keywrap {
+    use crate::{aes, from_hex, Error};
+    use anyhow::Result;
+
+    // RFC 3394 section 4
+    #[test]
+    fn test_kw() -> Result<()> {
+        let kek = from_hex("000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f")?;
+        let key = from_hex("00112233445566778899aabbccddeeff000102030405060708090a0b0c0d0e0f")?;
+
+        let kw = aes::Aes128KW::new(&kek[..16]);
+        let wrapped = kw.wrap_key(&key[..16])?;
+        assert_eq!(
+            wrapped,
+            from_hex("1fa68b0a8112b447aef34bd8fb5a7b829d3e862371d2cfe5")?
+        );
+        assert_eq!(kw.unwrap_key(&wrapped)?, &key[..16]);
+
+        let kw = aes::Aes192KW::new(&kek[..24]);
+        let wrapped = kw.wrap_key(&key[..16])?;
+        assert_eq!(
+            wrapped,
+            from_hex("96778b25ae6ca435f92b5b97c050aed2468ab8a17ad84e5d")?
+        );
+        assert_eq!(kw.unwrap_key(&wrapped)?, &key[..16]);
+
+        let kw = aes::Aes256KW::new(&kek);
+        let wrapped = kw.wrap_key(&key)?;
+        assert_eq!(
+            wrapped,
+            from_hex(
+                "28c9f404c4b810f4cbccb35cfb87f8263f5786e2d80ed326cbc7f0e71a99f43bfb988b9b7a02dd21"
+            )?
+        );
+        assert_eq!(kw.unwrap_key(&wrapped)?, key);
+
+        let mut tampered = wrapped.clone();
+        tampered[20] ^= 1;
+        assert!(matches!(
+            kw.unwrap_key(&tampered),
+            Err(Error::KeyWrapError(aes::KeyWrapError::IntegrityCheckFailed))
+        ));
+        assert!(matches!(
+            kw.wrap_key(&key[..12]),
+            Err(Error::KeyWrapError(aes::KeyWrapError::InvalidLength(12)))
+        ));
+
+        Ok(())
+    }
+
+    // RFC 5649 section 6
+    #[test]
+    fn test_kwp() -> Result<()> {
+        let kwp = aes::Aes192KWP::new(&from_hex(
+            "5840df6e29b02af1ab493b705bf16ea1ae8338f4dcc176a8",
+        )?);
+
+        let vectors = [
+            (
+                "c37b7e6492584340bed12207808941155068f738",
+                "138bdeaa9b8fa7fc61f97742e72248ee5ae6ae5360d1ae6a5f54f373fa543b6a",
+            ),
+            ("466f7250617369", "afbeb0f07dfbf5419200f2ccb50bb24f"),
+        ];
+
+        for (key, expected) in vectors {
+            let key = from_hex(key)?;
+            let wrapped = kwp.wrap_key(&key)?;
+            assert_eq!(wrapped, from_hex(expected)?);
+            assert_eq!(kwp.unwrap_key(&wrapped)?, key);
+
+            let mut tampered = wrapped.clone();
+            tampered[0] ^= 1;
+            assert!(matches!(
+                kwp.unwrap_key(&tampered),
+                Err(Error::KeyWrapError(aes::KeyWrapError::IntegrityCheckFailed))
+            ));
+        }
+
+        for len in 1..=33 {
+            let key: Vec<u8> = (0..len).collect();
+            assert_eq!(kwp.unwrap_key(&kwp.wrap_key(&key)?)?, key);
+        }
+        assert!(kwp.wrap_key(&[]).is_err());
+
+        Ok(())
+    }
+}
+
+mod xts {
+    use crate::{aes, from_hex};
+    use anyhow::Result;
+
+    // IEEE 1619-2007 Annex B, vectors 1, 2, 3 and 15 to 18
+    #[test]
+    fn test_xts() -> Result<()> {
+        let vectors = [
+            (
+                "0000000000000000000000000000000000000000000000000000000000000000",
+                0,
+                "0000000000000000000000000000000000000000000000000000000000000000",
+                "917cf69ebd68b2ec9b9fe9a3eadda692cd43d2f59598ed858c02c2652fbf922e",
+            ),
+            (
+                "1111111111111111111111111111111122222222222222222222222222222222",
+                0x3333333333,
+                "4444444444444444444444444444444444444444444444444444444444444444",
+                "c454185e6a16936e39334038acef838bfb186fff7480adc4289382ecd6d394f0",
+            ),
+            (
+                "fffefdfcfbfaf9f8f7f6f5f4f3f2f1f022222222222222222222222222222222",
+                0x3333333333,
+                "4444444444444444444444444444444444444444444444444444444444444444",
+                "af85336b597afc1a900b2eb21ec949d292df4c047e0b21532186a5971a227a89",
+            ),
+            (
+                "fffefdfcfbfaf9f8f7f6f5f4f3f2f1f0bfbebdbcbbbab9b8b7b6b5b4b3b2b1b0",
+                0x123456789a,
+                "000102030405060708090a0b0c0d0e0f10",
+                "6c1625db4671522d3d7599601de7ca09ed",
+            ),
+            (
+                "fffefdfcfbfaf9f8f7f6f5f4f3f2f1f0bfbebdbcbbbab9b8b7b6b5b4b3b2b1b0",
+                0x123456789a,
+                "000102030405060708090a0b0c0d0e0f1011",
+                "d069444b7a7e0cab09e24447d24deb1fedbf",
+            ),
+            (
+                "fffefdfcfbfaf9f8f7f6f5f4f3f2f1f0bfbebdbcbbbab9b8b7b6b5b4b3b2b1b0",
+                0x123456789a,
+                "000102030405060708090a0b0c0d0e0f101112",
+                "e5df1351c0544ba1350b3363cd8ef4beedbf9d",
+            ),
+            (
+                "fffefdfcfbfaf9f8f7f6f5f4f3f2f1f0bfbebdbcbbbab9b8b7b6b5b4b3b2b1b0",
+                0x123456789a,
+                "000102030405060708090a0b0c0d0e0f10111213",
+                "9d84c813f719aa2c7be3f66171c7c5c2edbf9dac",
+            ),
+        ];
+
+        for (key, sector, plaintext, expected) in vectors {
+            let xts = aes::Aes128Xts::new(&from_hex(key)?);
+            let plaintext = from_hex(plaintext)?;
+            let expected = from_hex(expected)?;
+
+            let mut buf = plaintext.clone();
+            xts.encrypt_in_place(&mut buf, sector)?;
+            assert_eq!(buf, expected);
+
+            xts.decrypt_in_place(&mut buf, sector)?;
+            assert_eq!(buf, plaintext);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_xts_sectors() -> Result<()> {
+        let key: Vec<u8> = (0..64).collect();
+        let xts = aes::Aes256Xts::new(&key);
+        let plaintext = [0x61; 512];
+
+        // The same data encrypts differently in every sector and position.
+        let mut first = [0u8; 512];
+        let mut second = [0u8; 512];
+        xts.encrypt(&plaintext, &mut first, 1)?;
+        xts.encrypt(&plaintext, &mut second, 2)?;
+        assert_ne!(first, second);
+        assert_ne!(first[..16], first[16..32]);
+
+        for len in [16, 17, 31, 32, 33, 100] {
+            let mut buf = plaintext[..len].to_vec();
+            xts.encrypt_in_place(&mut buf, 7)?;
+            xts.decrypt_in_place(&mut buf, 7)?;
+            assert_eq!(buf, &plaintext[..len]);
+        }
+
+        assert!(xts.encrypt_in_place(&mut [0u8; 15], 0).is_err());
+        assert!(aes::Aes128Xts::try_new(&key[..32 + 1]).is_err());
+
+        Ok(())
+    }
+}
+
+mod cbc_cs {
+    use crate::aes::{self, CiphertextStealing};
+    use crate::from_hex;
+    use anyhow::Result;
+
+    const PLAINTEXT: &[u8] = b"I would like the General Gau's Chicken, please, and wonton soup.";
+
+    // RFC 3962 Appendix B, which uses CS3
+    #[test]
+    fn test_cbc_cs3() -> Result<()> {
+        let cipher = aes::Aes128CBCCS::new(b"chicken teriyaki");
+        let iv = [0u8; 16];
+
+        let vectors = [
+            (17, "c6353568f2bf8cb4d8a580362da7ff7f97"),
+            (
+                31,
+                "fc00783e0efdb2c1d445d4c8eff7ed2297687268d6ecccc0c07b25e25ecfe5",
+            ),
+            (
+                32,
+                "39312523a78662d5be7fcbcc98ebf5a897687268d6ecccc0c07b25e25ecfe584",
+            ),
+            (
+                47,
+                "97687268d6ecccc0c07b25e25ecfe584b3fffd940c16a18c1b5549d2f838029e\
+                 39312523a78662d5be7fcbcc98ebf5",
+            ),
+        ];
+
+        for (len, expected) in vectors {
+            let mut buf = PLAINTEXT[..len].to_vec();
+            cipher.encrypt_in_place(&mut buf, &iv, CiphertextStealing::CS3)?;
+            assert_eq!(buf, from_hex(expected)?, "{len} bytes");
+
+            cipher.decrypt_in_place(&mut buf, &iv, CiphertextStealing::CS3)?;
+            assert_eq!(buf, &PLAINTEXT[..len]);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cbc_cs_lengths() -> Result<()> {
+        let key = b"YELLOW SUBMARINE";
+        let iv = *b"ICE ICE BABY ICE";
+        let cipher = aes::Aes128CBCCS::new(key);
+        let cbc = aes::Aes128CBC::new(key);
+
+        for len in 16..=PLAINTEXT.len() {
+            let plaintext = &PLAINTEXT[..len];
+
+            // Plain CBC of the zero padded message, which every variant
+            // rearranges.
+            let mut padded = plaintext.to_vec();
+            padded.resize(len.next_multiple_of(16), 0);
+            cbc.encrypt_in_place(&mut padded, &iv)?;
+            let blocks = padded.len() / 16;
+            let partial = len - 16 * (blocks - 1);
+            let head = &padded[..16 * blocks.saturating_sub(2)];
+            let (second_last, last) = padded[head.len()..].split_at(16.min(padded.len() - 16));
+
+            for variant in [
+                CiphertextStealing::CS1,
+                CiphertextStealing::CS2,
+                CiphertextStealing::CS3,
+            ] {
+                let mut output = vec![0u8; len];
+                cipher.encrypt(plaintext, &mut output, &iv, variant)?;
+                assert_eq!(output.len(), len);
+                assert_eq!(&output[..head.len()], head);
+
+                let expected = match (variant, len) {
+                    (_, 16) => padded.clone(),
+                    (CiphertextStealing::CS1, _) => [head, &second_last[..partial], last].concat(),
+                    (CiphertextStealing::CS2, _) if partial == 16 => padded.clone(),
+                    _ => [head, last, &second_last[..partial]].concat(),
+                };
+                assert_eq!(output, expected, "{variant:?}, {len} bytes");
+
+                let mut decrypted = vec![0u8; len];
+                cipher.decrypt(&output, &mut decrypted, &iv, variant)?;
+                assert_eq!(decrypted, plaintext, "{variant:?}, {len} bytes");
+            }
+        }
+
+        let mut short = [0u8; 15];
+        assert!(cipher
+            .encrypt_in_place(&mut short, &iv, CiphertextStealing::CS1)
+            .is_err());
+
+        Ok(())
+    }
+}
+
+mod padding {
+    use crate::aes::{
+        self, AnsiX923, Iso10126, Iso7816, Padding, PaddingError, Pkcs7, ZeroPadding,
+    };
+    use anyhow::Result;
+
+    fn padded<P: Padding>(data: &[u8]) -> Vec<u8> {
+        let mut buf = data.to_vec();
+        P::pad(&mut buf, 8);
+        buf
+    }
+
+    #[test]
+    fn test_pad() {
+        assert_eq!(padded::<Pkcs7>(b"YELLOW"), b"YELLOW\x02\x02");
+        assert_eq!(padded::<AnsiX923>(b"YELLOW"), b"YELLOW\x00\x02");
+        assert_eq!(padded::<Iso7816>(b"YELLOW"), b"YELLOW\x80\x00");
+        assert_eq!(padded::<ZeroPadding>(b"YELLOW"), b"YELLOW\x00\x00");
+        assert_eq!(padded::<ZeroPadding>(b"YELLOWSU"), b"YELLOWSU");
+        assert_eq!(
+            padded::<AnsiX923>(b"YELLOWSU"),
+            b"YELLOWSU\0\0\0\0\0\0\0\x08"
+        );
+        assert_eq!(padded::<Iso7816>(b""), b"\x80\0\0\0\0\0\0\0");
+
+        let iso10126 = padded::<Iso10126>(b"YELLOW SUB");
+        assert_eq!(iso10126.len(), 16);
+        assert_eq!(&iso10126[..10], b"YELLOW SUB");
+        assert_eq!(iso10126[15], 6);
+    }
+
+    fn check_roundtrip<P: Padding>() {
+        for len in 0..=24 {
+            let data: Vec<u8> = (1..=len).collect();
+            let mut buf = padded::<P>(&data);
+            assert!(buf.len().is_multiple_of(8));
+            P::unpad(&mut buf, 8).unwrap();
+            assert_eq!(buf, data);
+        }
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        check_roundtrip::<Pkcs7>();
+        check_roundtrip::<AnsiX923>();
+        check_roundtrip::<Iso7816>();
+        check_roundtrip::<Iso10126>();
+        check_roundtrip::<ZeroPadding>();
+    }
+
+    #[test]
+    fn test_strict_unpad() {
+        assert_eq!(
+            AnsiX923::unpadded_len(b"YELLOW\x01\x02", 8),
+            Err(PaddingError::InconsistentPadding)
+        );
+        assert_eq!(
+            AnsiX923::unpadded_len(b"YELLOW\x00\x09", 8),
+            Err(PaddingError::InvalidPadByte(9))
+        );
+        assert_eq!(
+            Iso7816::unpadded_len(b"YELLOW\x81\x00", 8),
+            Err(PaddingError::InvalidPadByte(0x81))
+        );
+        assert_eq!(
+            Iso7816::unpadded_len(b"YELLOW\x80\x00\0\0\0\0\0\0\0\0", 8),
+            Err(PaddingError::InvalidPadByte(0))
+        );
+        assert_eq!(Iso7816::unpadded_len(b"", 8), Err(PaddingError::Empty));
+        assert_eq!(
+            Iso10126::unpadded_len(b"YELLOW\xff\x00", 8),
+            Err(PaddingError::InvalidPadByte(0))
+        );
+        assert_eq!(Iso10126::unpadded_len(b"YELLOW\xff\x02", 8), Ok(6));
+        assert_eq!(
+            ZeroPadding::unpadded_len(b"YELLOW\0", 8),
+            Err(PaddingError::NotBlockAligned)
+        );
+
+        let mut buf = b"YELLOW\x03\x03".to_vec();
+        assert!(Pkcs7::unpad(&mut buf, 8).is_err());
+        assert_eq!(buf, b"YELLOW\x03\x03");
+    }
+
+    fn check_not_aligned<P: Padding>() {
+        assert_eq!(
+            P::unpadded_len(b"YELLOW\x02\x02", 16),
+            Err(PaddingError::NotBlockAligned)
+        );
+        assert_eq!(
+            P::unpadded_len(b"YELLOW\x80\x00", 16),
+            Err(PaddingError::NotBlockAligned)
+        );
+    }
+
+    #[test]
+    fn test_not_aligned() {
+        check_not_aligned::<Pkcs7>();
+        check_not_aligned::<AnsiX923>();
+        check_not_aligned::<Iso7816>();
+        check_not_aligned::<Iso10126>();
+        check_not_aligned::<ZeroPadding>();
+
+        assert_eq!(
+            aes::unpad(b"YELLOW\x02\x02", 16),
+            Err(PaddingError::NotBlockAligned)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "at most 255")]
+    fn test_block_size_too_large() {
+        Pkcs7::pad(&mut Vec::new(), 256);
+    }
+
+    #[test]
+    fn test_padded_modes() -> Result<()> {
+        let key = b"YELLOW SUBMARINE";
+        let iv = *b"ICE ICE BABY ICE";
+        let plaintext = b"YELLOW SUBMARINE, ICE ICE BABY";
+
+        let ecb = aes::Aes128ECB::new(key);
+        let ciphertext = ecb.encrypt_padded_with::<Iso7816>(plaintext);
+        assert_eq!(ciphertext.len(), 32);
+        assert_eq!(ecb.decrypt_padded_with::<Iso7816>(&ciphertext)?, plaintext);
+        assert!(ecb.decrypt_padded(&ciphertext).is_err());
+
+        let cbc = aes::Aes128CBC::new(key);
+        let ciphertext = cbc.encrypt_padded_with::<AnsiX923>(plaintext, &iv);
+        assert_eq!(
+            cbc.decrypt_padded_with::<AnsiX923>(&ciphertext, &iv)?,
+            plaintext
+        );
+        assert_eq!(
+            cbc.encrypt_padded_with::<Pkcs7>(plaintext, &iv),
+            cbc.encrypt_padded(plaintext, &iv)
+        );
+
+        Ok(())
+    }
+}
+
+mod siv {
+    use crate::{aes, from_hex, Error};
+    use anyhow::Result;
+
+    // RFC 5297 Appendix A.1
+    #[test]
+    fn test_siv_deterministic() -> Result<()> {
+        let siv = aes::Aes128Siv::new(&from_hex(
+            "fffefdfcfbfaf9f8f7f6f5f4f3f2f1f0f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff",
+        )?);
+        let ad = from_hex("101112131415161718191a1b1c1d1e1f2021222324252627")?;
+        let plaintext = from_hex("112233445566778899aabbccddee")?;
+        let expected = from_hex("85632d07c6e8f37f950acd320a2ecc9340c02b9690c4dc04daef7f6afe5c")?;
+
+        let ciphertext = siv.seal(&[&ad], &plaintext);
+        assert_eq!(ciphertext, expected);
+        assert_eq!(siv.open(&[&ad], &ciphertext)?, plaintext);
+
+        Ok(())
+    }
+
+    // RFC 5297 Appendix A.2
+    #[test]
+    fn test_siv_nonce() -> Result<()> {
+        let siv = aes::Aes128Siv::new(&from_hex(
+            "7f7e7d7c7b7a79787776757473727170404142434445464748494a4b4c4d4e4f",
+        )?);
+        let ad1 = from_hex(
+            "00112233445566778899aabbccddeeffdeaddadadeaddadaffeeddccbbaa99887766554433221100",
+        )?;
+        let ad2 = from_hex("102030405060708090a0")?;
+        let nonce = from_hex("09f911029d74e35bd84156c5635688c0")?;
+        let plaintext = from_hex(
+            "7468697320697320736f6d6520706c61696e7465787420746f20656e6372797074207573696e67205349562d414553",
+        )?;
+        let expected = from_hex(
+            "7bdb6e3b432667eb06f4d14bff2fbd0fcb900f2fddbe404326601965c889bf17\
+             dba77ceb094fa663b7a3f748ba8af829ea64ad544a272e9c485b62a3fd5c0d",
+        )?;
+        let ad: [&[u8]; 3] = [&ad1, &ad2, &nonce];
+
+        let mut buf = plaintext.clone();
+        let iv = siv.seal_in_place(&ad, &mut buf);
+        assert_eq!([&iv[..], &buf].concat(), expected);
+        siv.open_in_place(&ad, &mut buf, &iv)?;
+        assert_eq!(buf, plaintext);
+
+        // The order of the components matters.
+        let swapped: [&[u8]; 3] = [&ad2, &ad1, &nonce];
+        assert!(matches!(
+            siv.open(&swapped, &expected),
+            Err(Error::AuthenticationError(_))
+        ));
+
+        let mut forged = expected.clone();
+        forged[20] ^= 1;
+        assert!(siv.open(&ad, &forged).is_err());
+        assert!(siv.open(&ad, &expected[..15]).is_err());
+
+        Ok(())
+    }
+}
+
+mod random {
+    use crate::random::{self, Rng, SecureRandom, SeededRng};
+    use anyhow::Result;
+
+    #[test]
+    fn test_seeded_rng() {
+        // Reference output of xoshiro256** seeded with SplitMix64(0).
+        let mut rng = SeededRng::new(0);
+        assert_eq!(rng.next_u64(), 0x99ec5f36cb75f2b4);
+        assert_eq!(rng.next_u64(), 0xbf6e1f784956452a);
+
+        let a = SeededRng::new(42).random_bytes(37);
+        let b = SeededRng::new(42).random_bytes(37);
+        assert_eq!(a, b);
+        assert_ne!(a, SeededRng::new(43).random_bytes(37));
+    }
+
+    #[test]
+    fn test_gen_range() {
+        let mut rng = SeededRng::new(1);
+        let mut seen = [false; 6];
+
+        for _ in 0..1000 {
+            let x = rng.gen_range(5..11);
+            assert!((5..11).contains(&x));
+            seen[x - 5] = true;
+        }
+
+        assert!(seen.iter().all(|&s| s));
+    }
+
+    #[test]
+    fn test_secure_random() -> Result<()> {
+        let mut rng = SecureRandom::new()?;
+        assert_ne!(rng.random_bytes(32), rng.random_bytes(32));
+
+        // Large buffers are filled completely.
+        let mut buf = vec![0u8; 1 << 20];
+        rng.try_fill_bytes(&mut buf)?;
+        assert!(buf[buf.len() - 64..].iter().any(|&b| b != 0));
+        random::try_fill_random(&mut buf[..16])?;
+
+        assert_ne!(random::random_key(), random::random_key());
+        assert_ne!(random::random_iv(), random::random_iv());
+        assert_eq!(random::random_bytes(5).len(), 5);
+        assert_ne!(random::random_nonce::<12>(), [0u8; 12]);
+
+        Ok(())
+    }
+}
+
+mod oracle {
+    use crate::aes;
+    use crate::oracle::{self, BlockMode, Detection};
+    use crate::random::SeededRng;
+
+    #[test]
+    fn test_count_repeated_blocks() {
+        assert_eq!(oracle::count_repeated_blocks(&[], 16), 0);
+        assert_eq!(oracle::count_repeated_blocks(&[0u8; 64], 16), 3);
+        assert_eq!(oracle::count_repeated_blocks(&[1, 2, 1, 2, 3, 4, 1], 2), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "block size must not be zero")]
+    fn test_count_repeated_blocks_zero_block_size() {
+        oracle::count_repeated_blocks(&[0u8; 16], 0);
+    }
+
+    #[test]
+    fn test_encryption_oracle() {
+        let mut rng = SeededRng::new(0);
+        let mut modes = Vec::new();
+
+        for _ in 0..32 {
+            let (ciphertext, mode) = oracle::encryption_oracle(&mut rng, b"YELLOW SUBMARINE");
+
+            // 16 bytes of input, 10 to 20 bytes around it, then padding.
+            assert!(ciphertext.len() == 32 || ciphertext.len() == 48);
+            modes.push(mode);
+        }
+
+        assert!(modes.contains(&BlockMode::ECB));
+        assert!(modes.contains(&BlockMode::CBC));
+    }
+
+    #[test]
+    fn test_detect_mode_confidence() {
+        let cipher = aes::Aes128CBC::new(&[0u8; 16]);
+
+        // A CBC oracle that repeats half of its blocks looks like weak ECB.
+        let detection = oracle::detect_mode(|input| {
+            let ciphertext = cipher.encrypt_padded(input, &[0u8; 16]);
+            let mut blocks: Vec<_> = ciphertext.chunks(16).collect();
+            let first = blocks[0];
+            blocks[1..5].fill(first);
+
+            blocks.concat()
+        });
+
+        assert_eq!(
+            detection,
+            Detection {
+                mode: BlockMode::ECB,
+                confidence: 4.0 / 7.0
+            }
+        );
+    }
+}
+
+mod modes {
+    use crate::modes::{BlockCipher, Cipher, CounterLayout, CBC, CTR, ECB};
+
+    /// A toy 64-bit block cipher, only here to show that the modes do not
+    /// depend on AES.
+    struct RotateXor(u64);
+
+    impl BlockCipher for RotateXor {
+        type Block = [u8; 8];
+
+        const BLOCK_SIZE: usize = 8;
+
+        fn encrypt_block(&self, block: &mut [u8; 8]) {
+            *block = (u64::from_le_bytes(*block) ^ self.0)
+                .rotate_left(13)
+                .to_le_bytes();
+        }
+
+        fn decrypt_block(&self, block: &mut [u8; 8]) {
+            *block = (u64::from_le_bytes(*block).rotate_right(13) ^ self.0).to_le_bytes();
+        }
+    }
+
+    #[test]
+    fn test_generic_modes() {
+        let plaintext = b"YELLOW SUBMARINEYELLOW SUBMARINE";
+        let iv = *b"\x01\x23\x45\x67\x89\xab\xcd\xef";
+        let mut ciphertext = vec![0u8; plaintext.len()];
+        let mut output = vec![0u8; plaintext.len()];
+
+        let ecb = Cipher::<_, ECB>::from_cipher(RotateXor(0xdeadbeef));
+        ecb.encrypt(plaintext, &mut ciphertext).unwrap();
+        assert_eq!(ciphertext[..8], ciphertext[16..24]);
+        ecb.decrypt(&ciphertext, &mut output).unwrap();
+        assert_eq!(output, plaintext);
+
+        let cbc = Cipher::<_, CBC>::from_cipher(RotateXor(0xdeadbeef));
+        cbc.encrypt(plaintext, &mut ciphertext, &iv).unwrap();
+        assert_ne!(ciphertext[..8], ciphertext[16..24]);
+        cbc.decrypt(&ciphertext, &mut output, &iv).unwrap();
+        assert_eq!(output, plaintext);
+
+        let ctr = Cipher::<_, CTR>::from_cipher(RotateXor(0xdeadbeef));
+        let layout = CounterLayout::BigEndian32;
+        ctr.encrypt(&plaintext[..29], &mut ciphertext[..29], &iv, layout)
+            .unwrap();
+        ctr.decrypt(&ciphertext[..29], &mut output[..29], &iv, layout)
+            .unwrap();
+        assert_eq!(output[..29], plaintext[..29]);
+    }
+}
+
+mod stream {
+    use std::io::{Read, Write};
+
+    use crate::aes::{self, AesCore, CounterLayout, Key128};
+    use crate::modes::{CtrState, Decryptor, Encryptor, StreamingMode};
+    use anyhow::Result;
+
+    const KEY: &[u8; 16] = b"YELLOW SUBMARINE";
+
+    /// Streams `plaintext` through `MODE` in pieces that do not line up with
+    /// the blocks, checks that it decrypts back and returns the ciphertext.
+    fn stream_round_trip<MODE>(plaintext: &[u8], state: impl Fn() -> MODE::State) -> Result<Vec<u8>>
+    where
+        MODE: StreamingMode<AesCore<Key128>>,
+    {
+        let encryptor = Encryptor::new(Vec::new(), aes::Aes128::<MODE>::new(KEY), state());
+        let ciphertext = write_in_pieces(encryptor, plaintext)?.finish()?;
+
+        let mut output = Vec::new();
+        Decryptor::new(&ciphertext[..], aes::Aes128::<MODE>::new(KEY), state())
+            .read_to_end(&mut output)?;
+        assert_eq!(output, plaintext);
+
+        Ok(ciphertext)
+    }
+
+    /// Accepts `budget` bytes, then fails `failures` times, then accepts
+    /// everything.
+    struct FlakyWriter {
+        written: Vec<u8>,
+        budget: usize,
+        failures: usize,
+    }
+
+    impl Write for FlakyWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            if self.budget == 0 && self.failures > 0 {
+                self.failures -= 1;
+                return Err(std::io::ErrorKind::BrokenPipe.into());
+            }
+
+            let n = if self.failures > 0 {
+                buf.len().min(self.budget)
+            } else {
+                buf.len()
+            };
+            self.budget = self.budget.saturating_sub(n);
+            self.written.extend_from_slice(&buf[..n]);
+
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn write_in_pieces<W: Write>(mut writer: W, data: &[u8]) -> Result<W> {
+        // Uneven piece sizes so that blocks get split across writes.
+        for piece in data.chunks(7) {
+            writer.write_all(piece)?;
+        }
+
+        Ok(writer)
+    }
+
+    #[test]
+    fn test_cbc_stream() -> Result<()> {
+        let iv = [7u8; 16];
+        let plaintext: Vec<u8> = (0..20_000).map(|i| i as u8).collect();
+
+        let encryptor = Encryptor::new(Vec::new(), aes::Aes128CBC::new(KEY), iv);
+        let ciphertext = write_in_pieces(encryptor, &plaintext)?.finish()?;
+        assert_eq!(
+            ciphertext,
+            aes::Aes128CBC::new(KEY).encrypt_padded(&plaintext, &iv)
+        );
+
+        let mut decryptor = Decryptor::new(&ciphertext[..], aes::Aes128CBC::new(KEY), iv);
+        let mut output = Vec::new();
+        decryptor.read_to_end(&mut output)?;
+        assert_eq!(output, plaintext);
+
+        // Truncated input and bad padding are reported at the end of the stream.
+        let truncated = &ciphertext[..ciphertext.len() - 1];
+        let mut decryptor = Decryptor::new(truncated, aes::Aes128CBC::new(KEY), iv);
+        assert!(decryptor.read_to_end(&mut Vec::new()).is_err());
+
+        let mut unpadded = [0u8; 32];
+        aes::Aes128CBC::new(KEY).encrypt(&[0u8; 32], &mut unpadded, &iv)?;
+        let mut decryptor = Decryptor::new(&unpadded[..], aes::Aes128CBC::new(KEY), iv);
+        assert!(decryptor.read_to_end(&mut Vec::new()).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ecb_stream_empty() -> Result<()> {
+        let ciphertext = Encryptor::new(Vec::new(), aes::Aes128ECB::new(KEY), ()).finish()?;
+        assert_eq!(ciphertext.len(), 16);
+
+        let mut output = Vec::new();
+        Decryptor::new(&ciphertext[..], aes::Aes128ECB::new(KEY), ()).read_to_end(&mut output)?;
+        assert!(output.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unpadded_streams() -> Result<()> {
+        let iv = [3u8; 16];
+        let plaintext: Vec<u8> = (0..1000).map(|i| (i * 7) as u8).collect();
+        let mut expected = vec![0u8; plaintext.len()];
+
+        let ciphertext = stream_round_trip::<aes::CFB>(&plaintext, || iv)?;
+        aes::Aes128CFB::new(KEY).encrypt(&plaintext, &mut expected, &iv)?;
+        assert_eq!(ciphertext, expected);
+
+        let ciphertext = stream_round_trip::<aes::CFB8>(&plaintext, || iv)?;
+        aes::Aes128CFB8::new(KEY).encrypt(&plaintext, &mut expected, &iv)?;
+        assert_eq!(ciphertext, expected);
+
+        let ciphertext = stream_round_trip::<aes::OFB>(&plaintext, || iv)?;
+        aes::Aes128OFB::new(KEY).encrypt(&plaintext, &mut expected, &iv)?;
+        assert_eq!(ciphertext, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pcbc_stream() -> Result<()> {
+        let iv = [5u8; 16];
+        let plaintext: Vec<u8> = (0..1000).map(|i| (i * 11) as u8).collect();
+
+        let ciphertext = stream_round_trip::<aes::PCBC>(&plaintext, || iv)?;
+
+        let padded = aes::pad(&plaintext, 16);
+        let mut expected = vec![0u8; padded.len()];
+        aes::Aes128PCBC::new(KEY).encrypt(&padded, &mut expected, &iv)?;
+        assert_eq!(ciphertext, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_stream_padding() -> Result<()> {
+        let iv = [1u8; 16];
+        let plaintext = b"YELLOW SUBMARINE, YELLOW";
+
+        let encryptor =
+            Encryptor::new(Vec::new(), aes::Aes128CBC::new(KEY), iv).with_padding::<aes::Iso7816>();
+        let ciphertext = write_in_pieces(encryptor, plaintext)?.finish()?;
+        assert_eq!(
+            ciphertext,
+            aes::Aes128CBC::new(KEY).encrypt_padded_with::<aes::Iso7816>(plaintext, &iv)
+        );
+
+        let mut output = Vec::new();
+        Decryptor::new(&ciphertext[..], aes::Aes128CBC::new(KEY), iv)
+            .with_padding::<aes::Iso7816>()
+            .read_to_end(&mut output)?;
+        assert_eq!(output, plaintext);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_stream_write_error() -> Result<()> {
+        let iv = [9u8; 16];
+        let plaintext: Vec<u8> = (0..100).map(|i| i as u8).collect();
+        let writer = FlakyWriter {
+            written: Vec::new(),
+            budget: 20,
+            failures: 2,
+        };
+
+        // The first failure comes after the input was encrypted, so the bytes
+        // count as written. The second one happens before anything new is
+        // consumed and is returned.
+        let mut encryptor = Encryptor::new(writer, aes::Aes128CBC::new(KEY), iv);
+        assert_eq!(encryptor.write(&plaintext[..40])?, 40);
+        assert!(encryptor.write(&plaintext[40..]).is_err());
+        encryptor.write_all(&plaintext[40..])?;
+
+        let writer = encryptor.finish()?;
+        assert_eq!(
+            writer.written,
+            aes::Aes128CBC::new(KEY).encrypt_padded(&plaintext, &iv)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ctr_stream() -> Result<()> {
+        let iv = [0u8; 16];
+        let layout = CounterLayout::LittleEndian64;
+        let plaintext: Vec<u8> = (0..1000).map(|i| (i * 3) as u8).collect();
+
+        let encryptor = Encryptor::new(
+            Vec::new(),
+            aes::Aes128CTR::new(KEY),
+            CtrState::new(iv, layout),
+        );
+        let ciphertext = write_in_pieces(encryptor, &plaintext)?.finish()?;
+        assert_eq!(ciphertext.len(), plaintext.len());
+
+        let mut expected = vec![0u8; plaintext.len()];
+        aes::Aes128CTR::new(KEY).encrypt(&plaintext, &mut expected, &iv, layout)?;
+        assert_eq!(ciphertext, expected);
+
+        let mut output = Vec::new();
+        Decryptor::new(
+            &ciphertext[..],
+            aes::Aes128CTR::new(KEY),
+            CtrState::new(iv, layout),
+        )
+        .read_to_end(&mut output)?;
+        assert_eq!(output, plaintext);
+
+        Ok(())
+    }
 }
@@ -36,6 +36,30 @@ pub fn xor(a: &[u8], b: &[u8]) -> Vec<u8> {
     res
 }
 
+/// Returns the bytewise xor of the buffers, or an error if their lengths
+/// differ.
+pub fn try_xor(a: &[u8], b: &[u8]) -> Result<Vec<u8>, crate::Error> {
+    let mut res = a.to_vec();
+    try_xor_in_place(&mut res, b)?;
+
+    Ok(res)
+}
+
+/// Computes the bytewise xor of the two buffers inplace, or returns an error
+/// if their lengths differ.
+pub fn try_xor_in_place(a: &mut [u8], b: &[u8]) -> Result<(), crate::Error> {
+    if a.len() != b.len() {
+        return Err(crate::Error::LengthMismatch {
+            input: b.len(),
+            output: a.len(),
+        });
+    }
+
+    xor_in_place(a, b);
+
+    Ok(())
+}
+
 /// Computes the bytewise xor of the two buffers inplace.
 ///
 /// # Panics
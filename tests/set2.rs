@@ -19,11 +19,36 @@ fn challenge10() -> Result<()> {
     let key = b"YELLOW SUBMARINE";
 
     let cipher = aes::Aes128CBC::new(key);
-    let mut output = vec![0u8; input.len()];
-    cipher.decrypt(&input, &mut output, &[0u8; 16]);
+    let output = cipher.decrypt_padded(&input, &[0u8; 16])?;
 
     let plaintext = std::str::from_utf8(&output)?;
     expect_file!["../testdata/set2/10.out.txt"].assert_eq(plaintext);
 
     Ok(())
 }
+
+#[test]
+fn challenge11() {
+    let mut rng = random::SeededRng::new(11);
+
+    for _ in 0..100 {
+        let mut actual = None;
+        let detection = oracle::detect_mode(|input| {
+            let (ciphertext, mode) = oracle::encryption_oracle(&mut rng, input);
+            actual = Some(mode);
+            ciphertext
+        });
+
+        assert_eq!(Some(detection.mode), actual);
+        assert_eq!(detection.confidence, 1.0);
+    }
+}
+
+#[test]
+fn challenge15() {
+    let valid = b"ICE ICE BABY\x04\x04\x04\x04";
+    assert_eq!(aes::unpad(valid, 16).unwrap(), b"ICE ICE BABY");
+
+    assert!(aes::unpad(b"ICE ICE BABY\x05\x05\x05\x05", 16).is_err());
+    assert!(aes::unpad(b"ICE ICE BABY\x01\x02\x03\x04", 16).is_err());
+}
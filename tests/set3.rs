@@ -0,0 +1,26 @@
+use cryptopals_rs::*;
+
+use anyhow::Result;
+use expect_test::expect;
+
+#[test]
+fn challenge18() -> Result<()> {
+    let input = base64::from_base64(
+        "L77na/nrFsKvynd6HzOoG7GHTLXsTVu9qvY/2syLXzhPweyyMTJULu/6/kXX0KSvoOLSFQ==",
+    )?;
+    let key = b"YELLOW SUBMARINE";
+
+    let cipher = aes::Aes128CTR::new(key);
+    let mut output = vec![0u8; input.len()];
+    cipher.decrypt(
+        &input,
+        &mut output,
+        &[0u8; 16],
+        aes::CounterLayout::LittleEndian64,
+    )?;
+
+    let plaintext = std::str::from_utf8(&output)?;
+    expect!["Yo, VIP Let's kick it Ice, Ice, baby Ice, Ice, baby "].assert_eq(plaintext);
+
+    Ok(())
+}